@@ -1,48 +1,122 @@
-use std::rc::Rc;
+use std::{cell::RefCell, path::PathBuf, rc::Rc, sync::Arc};
 
 use anyhow::{Context, anyhow};
 
 use crate::{
-	config::{self, Config},
+	apply_report::ApplyReport,
+	config::{self, Config, ConfigOverride},
 	files::Files,
-	module_manager::{ApplyParams, ModuleManager, ModuleManagerInit},
-	state_manager::StateManager,
+	hooks::{HookContext, HookEvent},
+	lua::runtime::LuaRuntimeInit,
+	module_manager::{ApplyParams, ModuleListing, ModuleManager, ModuleManagerInit},
+	module_test::ModuleTester,
+	scaffold,
+	state_manager::{HistoryEntry, StateManager},
 	theme::{ColorRef, Theme},
 	theme_manager::ThemeManager,
-	utils::xdg::XdgDirs,
+	utils::{
+		lock::{FileLock, LockMode},
+		xdg::XdgDirs,
+	},
 };
 
+#[derive(Debug, Default)]
+pub struct NijiAppInit {
+	pub lock_mode: Option<LockMode>,
+	pub config_file: Option<PathBuf>,
+	pub config_overrides: Vec<ConfigOverride>,
+}
+
 pub struct NijiApp {
-	_xdg: Rc<XdgDirs>,
-	_files: Rc<Files>,
+	xdg: Arc<XdgDirs>,
+	files: Arc<Files>,
 	config: Rc<Config>,
 	state_manager: StateManager,
 	theme_manager: Rc<ThemeManager>,
 	module_manager: Rc<ModuleManager>,
+
+	/// The theme and accent color when the app was initialized, which are
+	/// compared to the current ones to decide which change hooks to run.
+	initial_theme: Option<String>,
+	initial_accent: Option<String>,
+
+	/// The modules that were applied by the last apply, which are passed to
+	/// the change hooks.
+	applied_modules: RefCell<Vec<String>>,
+	_lock: Option<FileLock>,
 }
 
 impl NijiApp {
-	pub fn init() -> anyhow::Result<Self> {
-		let xdg = Rc::new(XdgDirs::new()?);
-		let files = Rc::new(Files::new(&xdg)?);
-		let config = Rc::new(config::read_config(files.config_file())?);
-		let state_manager = StateManager::new(Rc::clone(&files))?;
-		let theme_manager = Rc::new(ThemeManager::new(Rc::clone(&files)));
+	/// Initializes the app. If a `lock_mode` is given, the lock is held for as
+	/// long as the app exists, and the state is only read once it was acquired.
+	pub fn init(
+		NijiAppInit {
+			lock_mode,
+			config_file,
+			config_overrides,
+		}: NijiAppInit,
+	) -> anyhow::Result<Self> {
+		let xdg = Arc::new(XdgDirs::new()?);
+		let files = Arc::new(Files::new(&xdg)?);
+		let lock = lock_mode
+			.map(|mode| FileLock::acquire(files.lock_file(), mode))
+			.transpose()?;
+		// Only the default config file may be missing
+		let config_file = match config_file {
+			Some(config_file) if !config_file.exists() => {
+				return Err(anyhow!(
+					"Config file {} does not exist",
+					config_file.display()
+				));
+			}
+			Some(config_file) => config_file,
+			None => files.config_file().to_path_buf(),
+		};
+		let config = Rc::new(config::read_config(&config_file, &config_overrides)?);
+		let state_manager = StateManager::new(Arc::clone(&files))?;
+		let theme_manager = Rc::new(ThemeManager::new(Arc::clone(&files)));
 		let module_manager = Rc::new(ModuleManager::new(ModuleManagerInit {
-			xdg: Rc::clone(&xdg),
-			files: Rc::clone(&files),
-		})?);
+			xdg: Arc::clone(&xdg),
+			files: Arc::clone(&files),
+		}));
 
 		Ok(Self {
-			_xdg: xdg,
-			_files: files,
+			xdg,
+			files,
 			config,
+			initial_theme: state_manager.get_theme().map(ToString::to_string),
+			initial_accent: state_manager.get_accent().map(ToString::to_string),
 			state_manager,
 			theme_manager,
 			module_manager,
+			applied_modules: RefCell::default(),
+			_lock: lock,
 		})
 	}
 
+	/// Returns the path of the config file, which is the default one unless a
+	/// different `config_file` is given.
+	pub fn config_file_path(config_file: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+		if let Some(config_file) = config_file {
+			return Ok(config_file);
+		}
+		let xdg = XdgDirs::new()?;
+		let files = Files::new(&xdg)?;
+		Ok(files.config_file().to_path_buf())
+	}
+
+	pub fn get_config_value(&self, key: &[String]) -> anyhow::Result<toml::Value> {
+		let mut value = toml::Value::try_from(&*self.config)?;
+		for component in key {
+			value = match value {
+				toml::Value::Table(mut table) => table.remove(component),
+				_ => None,
+			}
+			.ok_or_else(|| anyhow!("{} is not set", key.join(config::KEY_SEPARATOR)))?;
+		}
+		Ok(value)
+	}
+
 	pub fn is_theme_set(&self) -> bool {
 		self.state_manager.get_theme().is_some()
 	}
@@ -60,6 +134,10 @@ impl NijiApp {
 			.context("Cannot get current theme")
 	}
 
+	pub fn get_current_theme_name(&self) -> Option<&str> {
+		self.state_manager.get_theme()
+	}
+
 	pub fn get_current_accent(&self) -> anyhow::Result<ColorRef> {
 		let name = self.state_manager.get_accent().ok_or(anyhow!(
 			"No accent color set; use `niji accent set <name>` to specify an accent color."
@@ -75,22 +153,182 @@ impl NijiApp {
 		self.theme_manager.list_themes()
 	}
 
-	pub fn apply_default(&self, params: &ApplyParams) -> anyhow::Result<()> {
-		self.apply(params, &self.config.modules)?;
-		Ok(())
+	pub fn list_modules(&self) -> Vec<ModuleListing> {
+		self.module_manager.list()
+	}
+
+	pub fn get_module_dir(&self, name: &str) -> anyhow::Result<PathBuf> {
+		self.module_manager.find(name)
 	}
 
-	pub fn apply(&self, params: &ApplyParams, modules: &[String]) -> anyhow::Result<()> {
+	/// Creates a new module from the scaffold in the custom modules directory,
+	/// and returns its path.
+	pub fn create_module(&self, name: &str) -> anyhow::Result<PathBuf> {
+		let path = self.files.custom_modules_dir().join(name);
+		if let Ok(existing) = self.module_manager.find(name)
+			&& existing != path
+		{
+			log::warn!(
+				"The new module will take precedence over the module at {}",
+				existing.display()
+			);
+		}
+		scaffold::create_module(&path, name)?;
+		Ok(path)
+	}
+
+	/// Tests a module against its snapshots, using the given theme, or every
+	/// built-in theme.
+	pub fn test_module(&self, name: &str, theme: Option<&str>, update: bool) -> anyhow::Result<()> {
+		let tester = ModuleTester::new(&self.xdg, &self.get_module_dir(name)?);
+		let themes = match theme {
+			Some(theme) => vec![self.get_theme(theme)?],
+			None => tester.builtin_themes()?,
+		};
+		if themes.is_empty() {
+			return Err(anyhow!("No built-in themes were found to test with"));
+		}
+		tester.run(&themes, update)
+	}
+
+	/// Returns the names of the modules in the `modules` list of the active
+	/// config, regardless of whether their conditions are met.
+	pub fn enabled_modules(&self) -> anyhow::Result<Vec<String>> {
+		let config = self.get_active_config()?;
+		Ok(config
+			.modules
+			.iter()
+			.map(|entry| entry.name().to_string())
+			.collect())
+	}
+
+	pub fn apply_default(&self, params: &ApplyParams) -> anyhow::Result<ApplyReport> {
+		let config = self.get_active_config()?;
+		self.apply_with_config(&config, params, &config.active_modules())
+	}
+
+	pub fn apply(&self, params: &ApplyParams, modules: &[String]) -> anyhow::Result<ApplyReport> {
+		let config = self.get_active_config()?;
+		self.apply_with_config(&config, params, modules)
+	}
+
+	fn apply_with_config(
+		&self,
+		config: &Config,
+		params: &ApplyParams,
+		modules: &[String],
+	) -> anyhow::Result<ApplyReport> {
 		let theme = self.get_current_theme()?;
 		let accent = self
 			.get_current_accent()?
 			.resolve(&theme.palette)
 			.context("Invalid accent color set")?;
 
+		let mut context = HookContext {
+			theme: Some(theme.clone()),
+			accent: self.state_manager.get_accent().map(ToString::to_string),
+			modules: modules.to_vec(),
+			previous: None,
+		};
+		if !params.dry_run {
+			config
+				.hooks
+				.run(HookEvent::PreApply, &context, &self.lua_runtime_init())?;
+		}
+
 		log::info!("Applying changes to target modules...");
 
-		self.module_manager
-			.apply(&self.config, &theme, accent, params, modules)?;
+		let report = self
+			.module_manager
+			.apply(config, &theme, accent, params, modules)?;
+
+		if !params.dry_run {
+			context.modules = report.applied_modules();
+			self.applied_modules.replace(context.modules.clone());
+			config
+				.hooks
+				.run(HookEvent::PostApply, &context, &self.lua_runtime_init())?;
+		}
+
+		Ok(report)
+	}
+
+	/// Runs the `on_theme_change` and `on_accent_change` hooks if the theme or
+	/// accent color changed since the app was initialized.
+	pub fn run_change_hooks(&self) -> anyhow::Result<()> {
+		let theme = self.state_manager.get_theme();
+		let accent = self.state_manager.get_accent();
+		let theme_changed = theme.is_some() && theme != self.initial_theme.as_deref();
+		let accent_changed = accent.is_some() && accent != self.initial_accent.as_deref();
+		if !theme_changed && !accent_changed {
+			return Ok(());
+		}
+
+		let config = self.get_active_config()?;
+		let mut context = HookContext {
+			theme: self.get_current_theme().ok(),
+			accent: accent.map(ToString::to_string),
+			modules: self.applied_modules.borrow().clone(),
+			previous: None,
+		};
+		if theme_changed {
+			context.previous.clone_from(&self.initial_theme);
+			config
+				.hooks
+				.run(HookEvent::ThemeChange, &context, &self.lua_runtime_init())?;
+		}
+		if accent_changed {
+			context.previous.clone_from(&self.initial_accent);
+			config
+				.hooks
+				.run(HookEvent::AccentChange, &context, &self.lua_runtime_init())?;
+		}
+		Ok(())
+	}
+
+	fn lua_runtime_init(&self) -> LuaRuntimeInit {
+		LuaRuntimeInit {
+			xdg: Arc::clone(&self.xdg),
+			files: Arc::clone(&self.files),
+		}
+	}
+
+	fn get_active_config(&self) -> anyhow::Result<Config> {
+		let Some(profile) = self.state_manager.get_profile() else {
+			return Ok((*self.config).clone());
+		};
+		if !self.config.profiles.contains_key(profile) {
+			log::warn!(
+				"The active profile '{profile}' no longer exists in your config; falling back to \
+				 the base configuration."
+			);
+			return Ok((*self.config).clone());
+		}
+		log::debug!("Using profile '{profile}'");
+		self.config.with_profile(profile)
+	}
+
+	pub fn get_current_profile(&self) -> Option<&str> {
+		self.state_manager.get_profile()
+	}
+
+	pub fn list_profiles(&self) -> Vec<&str> {
+		let mut profiles: Vec<&str> = self.config.profiles.keys().map(String::as_str).collect();
+		profiles.sort_unstable();
+		profiles
+	}
+
+	pub fn switch_profile(&mut self, name: &str) -> anyhow::Result<()> {
+		let profile = self.config.get_profile(name)?.clone();
+		self.state_manager.set_profile(name.to_string())?;
+		log::info!("Switched to profile '{name}'");
+
+		if let Some(theme) = &profile.theme {
+			self.set_current_theme(theme)?;
+		}
+		if let Some(accent) = &profile.accent {
+			self.set_current_accent(ColorRef::named(accent))?;
+		}
 		Ok(())
 	}
 
@@ -116,6 +354,33 @@ impl NijiApp {
 		Ok(())
 	}
 
+	pub fn get_history(&self) -> (&[HistoryEntry], usize) {
+		(
+			self.state_manager.get_history(),
+			self.state_manager.get_history_position(),
+		)
+	}
+
+	pub fn undo(&mut self) -> anyhow::Result<()> {
+		self.state_manager.undo()?;
+		log::info!("Reverted to {}", self.describe_state());
+		Ok(())
+	}
+
+	pub fn redo(&mut self) -> anyhow::Result<()> {
+		self.state_manager.redo()?;
+		log::info!("Restored {}", self.describe_state());
+		Ok(())
+	}
+
+	fn describe_state(&self) -> String {
+		format!(
+			"theme '{}' with accent '{}'",
+			self.state_manager.get_theme().unwrap_or("<unset>"),
+			self.state_manager.get_accent().unwrap_or("<unset>")
+		)
+	}
+
 	pub fn set_current_accent(&mut self, color: ColorRef) -> anyhow::Result<()> {
 		let ColorRef::Named(name) = color else {
 			return Err(anyhow!(
@@ -0,0 +1,187 @@
+use std::{fmt, time::Duration};
+
+use anyhow::anyhow;
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleStatus {
+	/// The module was applied, but not reloaded.
+	Applied,
+
+	/// The module was applied and reloaded.
+	Reloaded,
+
+	/// The module wasn't applied, either because nothing changed since the
+	/// last application, or because a module it depends on failed.
+	Skipped,
+
+	Failed,
+}
+
+impl fmt::Display for ModuleStatus {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Applied => write!(f, "applied"),
+			Self::Reloaded => write!(f, "reloaded"),
+			Self::Skipped => write!(f, "skipped"),
+			Self::Failed => write!(f, "failed"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleReport {
+	pub name: String,
+	pub status: ModuleStatus,
+
+	#[serde(rename = "duration_secs", serialize_with = "serialize_secs")]
+	pub duration: Duration,
+
+	/// Why the module was skipped or failed. For failures, this is the chain
+	/// of error messages, starting with the outermost one.
+	pub messages: Vec<String>,
+}
+
+impl ModuleReport {
+	pub fn failed(name: &str, duration: Duration, error: &anyhow::Error) -> Self {
+		Self {
+			name: name.to_string(),
+			status: ModuleStatus::Failed,
+			duration,
+			messages: error.chain().map(ToString::to_string).collect(),
+		}
+	}
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// The outcome of applying a set of modules, in the order in which they were
+/// applied.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApplyReport {
+	pub modules: Vec<ModuleReport>,
+}
+
+impl ApplyReport {
+	pub fn failures(&self) -> usize {
+		self.modules
+			.iter()
+			.filter(|module| module.status == ModuleStatus::Failed)
+			.count()
+	}
+
+	/// Returns the names of the modules that were applied successfully.
+	pub fn applied_modules(&self) -> Vec<String> {
+		self.modules
+			.iter()
+			.filter(|module| {
+				matches!(
+					module.status,
+					ModuleStatus::Applied | ModuleStatus::Reloaded
+				)
+			})
+			.map(|module| module.name.clone())
+			.collect()
+	}
+
+	pub fn print_summary(&self) {
+		if self.modules.is_empty() {
+			return;
+		}
+
+		let name_width = self
+			.modules
+			.iter()
+			.map(|module| module.name.len())
+			.max()
+			.unwrap_or_default()
+			.max("Module".len());
+
+		niji_console::heading!("Summary");
+		niji_console::println!(
+			"{:name_width$}  {:8}  {:>8}  Details",
+			"Module",
+			"Status",
+			"Time"
+		);
+		for module in &self.modules {
+			let duration = format!("{:.2}s", module.duration.as_secs_f64());
+			// Lua errors include a stack trace, which doesn't fit in the table
+			let details = module.messages.join(": ");
+			niji_console::println!(
+				"{:name_width$}  {:8}  {duration:>8}  {}",
+				module.name,
+				module.status.to_string(),
+				details.lines().next().unwrap_or_default()
+			);
+		}
+	}
+
+	/// Returns an error if any of the modules failed to apply.
+	pub fn check(&self) -> anyhow::Result<()> {
+		match self.failures() {
+			0 => Ok(()),
+			1 if self.modules.len() == 1 => Err(anyhow!("The module failed to apply")),
+			failures => Err(anyhow!(
+				"{failures} of {} modules failed to apply",
+				self.modules.len()
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn report(status: ModuleStatus) -> ModuleReport {
+		ModuleReport {
+			name: "test".to_string(),
+			status,
+			duration: Duration::ZERO,
+			messages: vec![],
+		}
+	}
+
+	#[test]
+	fn check_failures() {
+		let mut report = ApplyReport {
+			modules: vec![
+				report(ModuleStatus::Applied),
+				report(ModuleStatus::Reloaded),
+				report(ModuleStatus::Skipped),
+			],
+		};
+		report.check().unwrap();
+
+		report.modules.push(ModuleReport::failed(
+			"test",
+			Duration::ZERO,
+			&anyhow!("inner").context("outer"),
+		));
+		assert_eq!(report.failures(), 1);
+		assert_eq!(report.modules[3].messages, vec!["outer", "inner"]);
+		assert_eq!(
+			report.check().unwrap_err().to_string(),
+			"1 of 4 modules failed to apply"
+		);
+	}
+
+	#[test]
+	fn serialize_report() {
+		let report = ApplyReport {
+			modules: vec![ModuleReport {
+				duration: Duration::from_millis(1500),
+				..report(ModuleStatus::Reloaded)
+			}],
+		};
+
+		assert_eq!(
+			serde_json::to_string(&report).unwrap(),
+			r#"{"modules":[{"name":"test","status":"reloaded","duration_secs":1.5,"messages":[]}]}"#
+		);
+	}
+}
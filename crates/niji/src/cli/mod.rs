@@ -1,22 +1,40 @@
 #![allow(clippy::unused_self)]
 
-use std::process::ExitCode;
+use std::{
+	fs::File,
+	io::{self, Write},
+	os::fd::AsFd,
+	path::Path,
+	process::ExitCode,
+	sync::OnceLock,
+};
 
-use anyhow::anyhow;
+use anyhow::{Context, anyhow};
 use clap::Parser;
 use log::LevelFilter;
 use niji_console::ColorChoice;
+use serde::Serialize;
+use serde_json::json;
 mod syntax;
 
 use crate::{
-	app::NijiApp,
+	app::{NijiApp, NijiAppInit},
+	apply_report::ApplyReport,
 	cli::syntax::{
 		Accent, AccentCommand, AccentGet, AccentParams, AccentSet, AccentUnset, Apply, ApplyArgs,
-		GlobalArgs, Niji, NijiCommand, PaletteColor, Theme, ThemeCommand, ThemeGet, ThemeList,
-		ThemePreview, ThemeSet, ThemeUnset, UpdateArgs,
+		Config, ConfigCommand, ConfigEdit, ConfigGet, ConfigPath, ConfigSet, ConfigUnset,
+		GlobalArgs, History, Module, ModuleCommand, ModuleDisable, ModuleEnable, ModuleInfo,
+		ModuleList, ModuleNew, ModulePath, ModuleTest, Niji, NijiCommand, OutputFormat,
+		PaletteColor, Profile, ProfileCommand, ProfileCurrent, ProfileList, ProfileSwitch, Redo,
+		Status, Theme, ThemeCommand, ThemeGet, ThemeList, ThemePreview, ThemeSet, ThemeUnset, Undo,
+		UpdateArgs,
 	},
+	config::{self, ConfigOverride},
+	config_editor::{self, ConfigEditor},
 	module_manager::ApplyParams,
+	module_manifest::ModuleManifest,
 	theme::ColorRef,
+	utils::lock::LockMode,
 };
 
 #[must_use]
@@ -45,35 +63,104 @@ impl Niji {
 			ColorChoice::Auto
 		};
 
-		niji_console::init(level, color_choice);
+		// Keep stdout free for the JSON output
+		if self.global_args.json() {
+			reserve_stdout_for_json()?;
+			niji_console::init_stderr(level, color_choice);
+		} else {
+			niji_console::init(level, color_choice);
+		}
 
-		let mut app = NijiApp::init()?;
+		// Config commands must work even if the config is invalid
+		if let NijiCommand::Config(config) = &self.command {
+			return config.run(&self.global_args);
+		}
 
-		match &self.command {
-			NijiCommand::Apply(apply) => apply.run(&app),
+		let lock_mode = if self.global_args.lock_args.no_wait {
+			LockMode::NoWait
+		} else {
+			LockMode::Wait
+		};
+		let mut app = NijiApp::init(
+			self.global_args
+				.app_init(self.command.modifies_state().then_some(lock_mode))?,
+		)?;
+
+		let result = match &self.command {
+			NijiCommand::Apply(apply) => apply.run(&app, &self.global_args),
 			NijiCommand::Theme(theme) => theme.run(&mut app, &self.global_args),
-			NijiCommand::Accent(accent) => accent.run(&mut app),
+			NijiCommand::Accent(accent) => accent.run(&mut app, &self.global_args),
+			NijiCommand::Profile(profile) => profile.run(&mut app, &self.global_args),
+			NijiCommand::Config(_) => unreachable!(),
+			NijiCommand::Module(module) => module.run(&app, &self.global_args),
+			NijiCommand::Status(status) => status.run(&app, &self.global_args),
+			NijiCommand::History(history) => history.run(&app, &self.global_args),
+			NijiCommand::Undo(undo) => undo.run(&mut app, &self.global_args),
+			NijiCommand::Redo(redo) => redo.run(&mut app, &self.global_args),
+		};
+
+		// The theme or accent color may have changed even if applying failed
+		let hooks_result = app.run_change_hooks();
+		result.and(hooks_result)
+	}
+}
+
+impl GlobalArgs {
+	fn json(&self) -> bool {
+		self.output == OutputFormat::Json
+	}
+
+	fn app_init(&self, lock_mode: Option<LockMode>) -> anyhow::Result<NijiAppInit> {
+		let mut config_overrides = ConfigOverride::from_env()?;
+		for config_override in &self.set {
+			config_overrides.push(config_override.parse()?);
+		}
+
+		Ok(NijiAppInit {
+			lock_mode,
+			config_file: self.config.clone(),
+			config_overrides,
+		})
+	}
+}
+
+impl NijiCommand {
+	fn modifies_state(&self) -> bool {
+		match self {
+			Self::Theme(Theme { command }) => !matches!(
+				command,
+				ThemeCommand::Get(_) | ThemeCommand::List(_) | ThemeCommand::Preview(_)
+			),
+			Self::Accent(Accent { command }) => !matches!(command, AccentCommand::Get(_)),
+			Self::Profile(Profile { command }) => matches!(command, ProfileCommand::Switch(_)),
+			Self::Config(_) | Self::Module(_) | Self::Status(_) | Self::History(_) => false,
+			Self::Apply(apply) => !apply.dry_run,
+			Self::Undo(_) | Self::Redo(_) => true,
 		}
 	}
 }
 
 impl Apply {
-	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
-		let params = self.apply_args.apply_params();
-		if self.modules.is_empty() {
-			app.apply_default(&params)
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let params = ApplyParams {
+			dry_run: self.dry_run,
+			..self.apply_args.apply_params()
+		};
+		let report = if self.modules.is_empty() {
+			app.apply_default(&params)?
 		} else {
-			app.apply(&params, &self.modules)
-		}
+			app.apply(&params, &self.modules)?
+		};
+		finish_apply(&report, args)
 	}
 }
 
 impl Theme {
 	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		match &self.command {
-			ThemeCommand::Get(get) => get.run(app),
-			ThemeCommand::Set(set) => set.run(app),
-			ThemeCommand::List(list) => list.run(app),
+			ThemeCommand::Get(get) => get.run(app, args),
+			ThemeCommand::Set(set) => set.run(app, args),
+			ThemeCommand::List(list) => list.run(app, args),
 			ThemeCommand::Unset(unset) => unset.run(app),
 			ThemeCommand::Preview(preview) => preview.run(app, args),
 		}
@@ -81,8 +168,11 @@ impl Theme {
 }
 
 impl ThemeGet {
-	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		let theme = app.get_current_theme()?;
+		if args.json() {
+			return print_json(&theme);
+		}
 		niji_console::println!("{}", theme.name);
 		Ok(())
 	}
@@ -90,7 +180,7 @@ impl ThemeGet {
 
 impl ThemePreview {
 	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
-		if args.no_color {
+		if args.no_color && !args.json() {
 			return Err(anyhow!(
 				"Theme display is not supported in no-color mode. You can query the theme name by \
 				 using `niji theme get`."
@@ -113,6 +203,10 @@ impl ThemePreview {
 			app.get_current_accent()?.resolve(&theme.palette)?
 		};
 
+		if args.json() {
+			return print_json(&json!({ "theme": theme, "accent": accent_color }));
+		}
+
 		niji_console::println!("Theme \"{}\":", theme.name);
 		niji_console::println!();
 		niji_console::println!("Accent: {}", accent_color.preview());
@@ -123,7 +217,7 @@ impl ThemePreview {
 }
 
 impl ThemeSet {
-	fn run(&self, app: &mut NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		app.set_current_theme(&self.name)?;
 		if let Some(accent) = self.accent_args.accent_color() {
 			app.set_current_accent(accent)?;
@@ -135,7 +229,7 @@ impl ThemeSet {
 					"Cannot apply changes since no accent color is set. Consider using `niji theme set <name> --accent <color>` to set an accent color along with the theme, or use `niji theme set --no-apply <name>` to skip this step."
 				));
 			}
-			app.apply_default(&params)?;
+			finish_apply(&app.apply_default(&params)?, args)?;
 		}
 
 		Ok(())
@@ -143,11 +237,14 @@ impl ThemeSet {
 }
 
 impl ThemeList {
-	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		let themes = app.list_themes();
 		if themes.is_empty() {
 			return Err(anyhow!("No usable themes were found"));
 		}
+		if args.json() {
+			return print_json(&themes);
+		}
 
 		for theme in themes {
 			niji_console::println!("{theme}");
@@ -170,25 +267,33 @@ impl AccentParams {
 }
 
 impl Accent {
-	fn run(&self, app: &mut NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		match &self.command {
-			AccentCommand::Get(get) => get.run(app),
-			AccentCommand::Set(set) => set.run(app),
+			AccentCommand::Get(get) => get.run(app, args),
+			AccentCommand::Set(set) => set.run(app, args),
 			AccentCommand::Unset(unset) => unset.run(app),
 		}
 	}
 }
 
 impl AccentGet {
-	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		let color = app.get_current_accent()?;
+		if args.json() {
+			// The accent can only be resolved to a color if a theme is set
+			let resolved = match app.get_current_theme() {
+				Ok(theme) => Some(color.resolve(&theme.palette)?),
+				Err(_) => None,
+			};
+			return print_json(&json!({ "accent": color.to_string(), "color": resolved }));
+		}
 		niji_console::println!("{color}");
 		Ok(())
 	}
 }
 
 impl AccentSet {
-	fn run(&self, app: &mut NijiApp) -> anyhow::Result<()> {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
 		app.set_current_accent(self.color.into())?;
 		if let Some(params) = self.update_args.apply_params() {
 			if !app.is_theme_set() {
@@ -196,7 +301,7 @@ impl AccentSet {
 					"Cannot apply changes since no theme is set. Consider setting a theme using `niji theme set <name>`, or use `niji accent set --no-apply <color>` to skip this step."
 				));
 			}
-			app.apply_default(&params)?;
+			finish_apply(&app.apply_default(&params)?, args)?;
 		}
 		Ok(())
 	}
@@ -208,6 +313,530 @@ impl AccentUnset {
 	}
 }
 
+impl Profile {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		match &self.command {
+			ProfileCommand::Switch(switch) => switch.run(app, args),
+			ProfileCommand::List(list) => list.run(app, args),
+			ProfileCommand::Current(current) => current.run(app, args),
+		}
+	}
+}
+
+impl ProfileSwitch {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		app.switch_profile(&self.name)?;
+		if let Some(params) = self.update_args.apply_params() {
+			if !app.is_theme_set() || !app.is_accent_set() {
+				return Err(anyhow!(
+					"Cannot apply changes since no theme or accent color is set. Consider adding \
+					 a `theme` and `accent` to the profile, or use `niji profile switch \
+					 --no-apply <name>` to skip this step."
+				));
+			}
+			finish_apply(&app.apply_default(&params)?, args)?;
+		}
+		Ok(())
+	}
+}
+
+impl ProfileList {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let profiles = app.list_profiles();
+		if profiles.is_empty() {
+			return Err(anyhow!("No profiles are defined in your config"));
+		}
+		if args.json() {
+			return print_json(&profiles);
+		}
+
+		for profile in profiles {
+			niji_console::println!("{profile}");
+		}
+
+		Ok(())
+	}
+}
+
+impl ProfileCurrent {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let profile = app.get_current_profile().ok_or(anyhow!(
+			"No profile active; use `niji profile switch <name>` to activate a profile."
+		))?;
+		if args.json() {
+			return print_json(&json!({ "profile": profile }));
+		}
+		niji_console::println!("{profile}");
+		Ok(())
+	}
+}
+
+impl Config {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		match &self.command {
+			ConfigCommand::Get(get) => get.run(args),
+			ConfigCommand::Set(set) => set.run(args),
+			ConfigCommand::Unset(unset) => unset.run(args),
+			ConfigCommand::Edit(edit) => edit.run(args),
+			ConfigCommand::Path(path) => path.run(args),
+		}
+	}
+}
+
+impl ConfigGet {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let app = NijiApp::init(args.app_init(None)?)?;
+		let key = config::parse_key_path(&self.key, config::KEY_SEPARATOR)?;
+		let value = app.get_config_value(&key)?;
+		if args.json() {
+			return print_json(&value);
+		}
+		match value {
+			toml::Value::String(value) => niji_console::println!("{value}"),
+			toml::Value::Table(table) => {
+				niji_console::println!("{}", toml::to_string(&table)?.trim_end());
+			}
+			value => niji_console::println!("{value}"),
+		}
+		Ok(())
+	}
+}
+
+impl ConfigSet {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		let key = config::parse_key_path(&self.key, config::KEY_SEPARATOR)?;
+		let mut editor = ConfigEditor::open(&path)?;
+		editor.set(&key, &self.value)?;
+		editor.save()?;
+		log::info!("Set {} in {}", self.key, path.display());
+		Ok(())
+	}
+}
+
+impl ConfigUnset {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		let key = config::parse_key_path(&self.key, config::KEY_SEPARATOR)?;
+		let mut editor = ConfigEditor::open(&path)?;
+		editor.unset(&key)?;
+		editor.save()?;
+		log::info!("Removed {} from {}", self.key, path.display());
+		Ok(())
+	}
+}
+
+impl ConfigEdit {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		loop {
+			config_editor::open_in_editor(&path)?;
+			let Err(err) = config::read_config(&path, &[]) else {
+				return Ok(());
+			};
+			log::error!("{err:#}");
+			if !niji_console::prompt!(default: true, "Re-open the editor to fix the error?") {
+				return Err(anyhow!("The config in {} is invalid", path.display()));
+			}
+		}
+	}
+}
+
+impl ConfigPath {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		if args.json() {
+			return print_json(&json!({ "path": path }));
+		}
+		niji_console::println!("{}", path.display());
+		Ok(())
+	}
+}
+
+impl Module {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		match &self.command {
+			ModuleCommand::List(list) => list.run(app, args),
+			ModuleCommand::Info(info) => info.run(app, args),
+			ModuleCommand::Enable(enable) => enable.run(app, args),
+			ModuleCommand::Disable(disable) => disable.run(args),
+			ModuleCommand::Path(path) => path.run(app, args),
+			ModuleCommand::New(new) => new.run(app),
+			ModuleCommand::Test(test) => test.run(app),
+		}
+	}
+}
+
+impl ModuleList {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let modules = app.list_modules();
+		if modules.is_empty() {
+			return Err(anyhow!("No modules were found"));
+		}
+
+		let enabled = app.enabled_modules()?;
+		if args.json() {
+			let modules: Vec<_> = modules
+				.iter()
+				.map(|module| {
+					json!({
+						"name": module.name,
+						"path": module.path,
+						"enabled": !module.shadowed && enabled.contains(&module.name),
+						"shadowed": module.shadowed,
+					})
+				})
+				.collect();
+			return print_json(&modules);
+		}
+
+		let name_width = modules.iter().map(|m| m.name.len()).max().unwrap_or(0);
+		for module in modules {
+			let marker = if !module.shadowed && enabled.contains(&module.name) {
+				'*'
+			} else {
+				' '
+			};
+			let shadowed = if module.shadowed { " (shadowed)" } else { "" };
+			niji_console::println!(
+				"{marker} {:name_width$}  {}{shadowed}",
+				module.name,
+				module.path.display()
+			);
+		}
+
+		Ok(())
+	}
+}
+
+impl ModuleInfo {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = app.get_module_dir(&self.name)?;
+		let manifest = ModuleManifest::load(&path)?;
+		let enabled = app.enabled_modules()?.contains(&self.name);
+		if args.json() {
+			return print_json(&module_info_json(&self.name, &path, &manifest, enabled));
+		}
+
+		niji_console::println!("Module \"{}\":", self.name);
+		if let Some(description) = &manifest.description {
+			niji_console::println!("{description}");
+		}
+		niji_console::println!();
+		niji_console::println!("Path: {}", path.display());
+		niji_console::println!("Enabled: {}", if enabled { "yes" } else { "no" });
+		if let Some(version) = &manifest.version {
+			niji_console::println!("Version: {version}");
+		}
+		if let Some(author) = &manifest.author {
+			niji_console::println!("Author: {author}");
+		}
+		if let Some(api_version) = manifest.api_version {
+			niji_console::println!("API version: {api_version}");
+		}
+		if !manifest.dependencies.is_empty() {
+			niji_console::println!("Dependencies: {}", join(&manifest.dependencies));
+		}
+		if !manifest.optional_dependencies.is_empty() {
+			niji_console::println!(
+				"Optional dependencies: {}",
+				join(&manifest.optional_dependencies)
+			);
+		}
+		if !manifest.desktops.is_empty() {
+			niji_console::println!("Desktops: {}", manifest.desktops.join(", "));
+		}
+		if !manifest.before.is_empty() {
+			niji_console::println!("Applied before: {}", manifest.before.join(", "));
+		}
+		if !manifest.after.is_empty() {
+			niji_console::println!("Applied after: {}", manifest.after.join(", "));
+		}
+		niji_console::println!("Reload: {}", manifest.reload);
+
+		if let Some(schema) = &manifest.schema {
+			let mut options: Vec<_> = schema.0.iter().collect();
+			options.sort_by_key(|(name, _)| *name);
+
+			niji_console::println!();
+			niji_console::println!("Options:");
+			for (name, option) in options {
+				match &option.description {
+					Some(description) => {
+						niji_console::println!("  {name} ({}): {description}", option.types);
+					}
+					None => niji_console::println!("  {name} ({})", option.types),
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+fn module_info_json(
+	name: &str,
+	path: &Path,
+	manifest: &ModuleManifest,
+	enabled: bool,
+) -> serde_json::Value {
+	let mut options: Vec<_> = manifest
+		.schema
+		.iter()
+		.flat_map(|schema| &schema.0)
+		.map(|(name, option)| {
+			json!({
+				"name": name,
+				"types": option.types.to_string(),
+				"description": option.description,
+			})
+		})
+		.collect();
+	options.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+	json!({
+		"name": name,
+		"description": manifest.description,
+		"path": path,
+		"enabled": enabled,
+		"version": manifest.version,
+		"author": manifest.author,
+		"api_version": manifest.api_version,
+		"dependencies": to_strings(&manifest.dependencies),
+		"optional_dependencies": to_strings(&manifest.optional_dependencies),
+		"desktops": manifest.desktops,
+		"before": manifest.before,
+		"after": manifest.after,
+		"reload": manifest.reload.to_string(),
+		"options": options,
+	})
+}
+
+fn to_strings(items: &[impl ToString]) -> Vec<String> {
+	items.iter().map(ToString::to_string).collect()
+}
+
+fn join(items: &[impl ToString]) -> String {
+	to_strings(items).join(", ")
+}
+
+impl ModuleEnable {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		app.get_module_dir(&self.name)?;
+
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		let mut editor = ConfigEditor::open(&path)?;
+		if !editor.enable_module(&self.name)? {
+			log::info!("Module {} is already enabled", self.name);
+			return Ok(());
+		}
+		editor.save()?;
+		log::info!(
+			"Enabled module {}. Run `niji apply -M {}` to apply it.",
+			self.name,
+			self.name
+		);
+		Ok(())
+	}
+}
+
+impl ModuleDisable {
+	fn run(&self, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = NijiApp::config_file_path(args.config.clone())?;
+		let mut editor = ConfigEditor::open(&path)?;
+		if !editor.disable_module(&self.name)? {
+			return Err(anyhow!("Module {} is not enabled", self.name));
+		}
+		editor.save()?;
+		log::info!("Disabled module {}", self.name);
+		Ok(())
+	}
+}
+
+impl ModulePath {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let path = app.get_module_dir(&self.name)?;
+		if args.json() {
+			return print_json(&json!({ "path": path }));
+		}
+		niji_console::println!("{}", path.display());
+		Ok(())
+	}
+}
+
+impl ModuleNew {
+	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
+		let path = app.create_module(&self.name)?;
+		log::info!(
+			"Created module {} in {}. Run `niji module enable {}` to add it to your config.",
+			self.name,
+			path.display(),
+			self.name
+		);
+		Ok(())
+	}
+}
+
+impl ModuleTest {
+	fn run(&self, app: &NijiApp) -> anyhow::Result<()> {
+		app.test_module(&self.name, self.theme.as_deref(), self.update)
+	}
+}
+
+const NO_HISTORY: &str = "No changes to the theme or accent color were recorded yet";
+
+impl History {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let (entries, position) = app.get_history();
+		if args.json() {
+			let entries: Vec<_> = entries
+				.iter()
+				.enumerate()
+				.map(|(i, entry)| {
+					json!({
+						"timestamp": entry.timestamp,
+						"theme": entry.theme,
+						"accent": entry.accent,
+						"profile": entry.profile,
+						"current": i == position,
+					})
+				})
+				.collect();
+			return print_json(&entries);
+		}
+		if entries.is_empty() {
+			log::info!("{NO_HISTORY}");
+			return Ok(());
+		}
+
+		for (i, entry) in entries.iter().enumerate() {
+			let marker = if i == position { '*' } else { ' ' };
+			let timestamp = chrono::DateTime::parse_from_rfc3339(&entry.timestamp).map_or_else(
+				|_| entry.timestamp.clone(),
+				|t| t.format("%Y-%m-%d %H:%M:%S").to_string(),
+			);
+			let profile = entry
+				.profile
+				.as_ref()
+				.map_or_else(String::new, |p| format!(" [{p}]"));
+			niji_console::println!(
+				"{marker} {timestamp}  {} ({}){profile}",
+				entry.theme.as_deref().unwrap_or("<unset>"),
+				entry.accent.as_deref().unwrap_or("<unset>")
+			);
+		}
+		Ok(())
+	}
+}
+
+impl Status {
+	fn run(&self, app: &NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		let theme = app.get_current_theme_name();
+		let kind = app.get_current_theme().ok().map(|theme| theme.kind);
+		let accent = app
+			.is_accent_set()
+			.then(|| app.get_current_accent())
+			.transpose()?
+			.map(|accent| accent.to_string());
+		let profile = app.get_current_profile();
+
+		if args.json() {
+			return print_json(&json!({
+				"theme": theme,
+				"kind": kind,
+				"accent": accent,
+				"profile": profile,
+			}));
+		}
+
+		match (theme, kind) {
+			(Some(theme), Some(kind)) => niji_console::println!("Theme: {theme} ({kind})"),
+			(Some(theme), None) => niji_console::println!("Theme: {theme} (not found)"),
+			(None, _) => niji_console::println!("Theme: not set"),
+		}
+		niji_console::println!("Accent: {}", accent.as_deref().unwrap_or("not set"));
+		niji_console::println!("Profile: {}", profile.unwrap_or("none"));
+		Ok(())
+	}
+}
+
+impl Undo {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		if app.get_history().0.is_empty() {
+			log::info!("{NO_HISTORY}, so there is nothing to undo");
+			return Ok(());
+		}
+		app.undo()?;
+		apply_restored_state(app, &self.update_args, args)
+	}
+}
+
+impl Redo {
+	fn run(&self, app: &mut NijiApp, args: &GlobalArgs) -> anyhow::Result<()> {
+		if app.get_history().0.is_empty() {
+			log::info!("{NO_HISTORY}, so there is nothing to redo");
+			return Ok(());
+		}
+		app.redo()?;
+		apply_restored_state(app, &self.update_args, args)
+	}
+}
+
+fn apply_restored_state(
+	app: &NijiApp,
+	update_args: &UpdateArgs,
+	args: &GlobalArgs,
+) -> anyhow::Result<()> {
+	let Some(params) = update_args.apply_params() else {
+		return Ok(());
+	};
+	if !app.is_theme_set() || !app.is_accent_set() {
+		log::warn!(
+			"Not applying changes, since the restored state doesn't have both a theme and an \
+			 accent color set."
+		);
+		return Ok(());
+	}
+	finish_apply(&app.apply_default(&params)?, args)
+}
+
+/// Prints a summary of the applied modules, or the report as JSON, and fails
+/// if any of them failed.
+fn finish_apply(report: &ApplyReport, args: &GlobalArgs) -> anyhow::Result<()> {
+	if args.json() {
+		print_json(report)?;
+	} else {
+		report.print_summary();
+	}
+	report.check()
+}
+
+/// The original stdout in JSON mode, after stdout was redirected to stderr.
+static JSON_OUTPUT: OnceLock<File> = OnceLock::new();
+
+/// Redirects stdout to stderr, so that only the JSON output ends up in the
+/// original stdout, and not the output of the commands modules and hooks run.
+fn reserve_stdout_for_json() -> anyhow::Result<()> {
+	let stdout = io::stdout()
+		.as_fd()
+		.try_clone_to_owned()
+		.context("Failed to duplicate stdout")?;
+	rustix::stdio::dup2_stdout(io::stderr()).context("Failed to redirect stdout")?;
+	let _ = JSON_OUTPUT.set(File::from(stdout));
+	Ok(())
+}
+
+/// Writes `value` as a single line of JSON to stdout, bypassing the console,
+/// which writes to stderr in JSON mode.
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+	let json = serde_json::to_string(value)?;
+	match JSON_OUTPUT.get() {
+		Some(mut output) => writeln!(output, "{json}")?,
+		None => writeln!(io::stdout().lock(), "{json}")?,
+	}
+	Ok(())
+}
+
 impl From<PaletteColor> for ColorRef {
 	fn from(value: PaletteColor) -> Self {
 		match value {
@@ -239,6 +868,10 @@ impl ApplyArgs {
 		ApplyParams {
 			reload: !self.no_reload,
 			check_deps: !self.ignore_deps,
+			force: self.force,
+			dry_run: false,
+			fail_fast: self.fail_fast,
+			keep_partial: self.keep_partial,
 		}
 	}
 }
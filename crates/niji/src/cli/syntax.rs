@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
@@ -42,6 +44,60 @@ pub struct GlobalArgs {
 		help = "Disable colored output"
 	)]
 	pub no_color: bool,
+
+	#[arg(
+		short = 'c',
+		long = "config",
+		global = true,
+		help = "Use the given config file instead of the default one"
+	)]
+	pub config: Option<PathBuf>,
+
+	#[arg(
+		long = "set",
+		global = true,
+		value_name = "KEY=VALUE",
+		help = "Override a config entry, like `kitty.background_opacity=0.9`. The value uses TOML \
+		        syntax. Can be set multiple times."
+	)]
+	pub set: Vec<String>,
+
+	#[arg(
+		long = "output",
+		global = true,
+		value_name = "FORMAT",
+		default_value = "text",
+		help = "The format of the command output. With `json`, log messages are written to stderr."
+	)]
+	pub output: OutputFormat,
+
+	#[command(flatten)]
+	pub lock_args: LockArgs,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+	Text,
+	Json,
+}
+
+#[derive(Args, Debug)]
+pub struct LockArgs {
+	#[arg(
+		long = "wait",
+		global = true,
+		overrides_with = "no_wait",
+		help = "Wait for other running niji processes to finish before making changes (default)"
+	)]
+	pub wait: bool,
+
+	#[arg(
+		long = "no-wait",
+		global = true,
+		overrides_with = "wait",
+		help = "Fail immediately if another niji process is currently making changes"
+	)]
+	pub no_wait: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -49,9 +105,17 @@ pub enum NijiCommand {
 	Apply(Apply),
 	Theme(Theme),
 	Accent(Accent),
+	Profile(Profile),
+	Config(Config),
+	Module(Module),
+	Status(Status),
+	History(History),
+	Undo(Undo),
+	Redo(Redo),
 }
 
 #[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ApplyArgs {
 	#[arg(
 		short = 'k',
@@ -67,6 +131,34 @@ pub struct ApplyArgs {
 		help = "Ignore missing module dependencies"
 	)]
 	pub ignore_deps: bool,
+
+	#[arg(
+		short = 'f',
+		long = "force",
+		help = "Re-apply modules even if their inputs haven't changed since the last application"
+	)]
+	pub force: bool,
+
+	#[arg(
+		long = "keep-partial",
+		help = "Keep the files a module has written if applying it fails, instead of restoring \
+		        their previous versions"
+	)]
+	pub keep_partial: bool,
+
+	#[arg(
+		long = "fail-fast",
+		overrides_with = "keep_going",
+		help = "Apply the modules one after the other, and stop at the first module that fails"
+	)]
+	pub fail_fast: bool,
+
+	#[arg(
+		long = "keep-going",
+		overrides_with = "fail_fast",
+		help = "Keep applying the other modules when a module fails (default)"
+	)]
+	pub keep_going: bool,
 }
 
 #[derive(Args, Debug)]
@@ -85,6 +177,7 @@ pub struct UpdateArgs {
 
 #[derive(Parser, Debug)]
 #[command(about = "Apply (or re-apply) the current theme and configuration")]
+#[allow(clippy::struct_field_names)]
 pub struct Apply {
 	#[arg(
 		short = 'M',
@@ -94,6 +187,13 @@ pub struct Apply {
 	)]
 	pub modules: Vec<String>,
 
+	#[arg(
+		long = "dry-run",
+		help = "Print the changes to files and the reload commands that applying would result in, \
+		        without making them"
+	)]
+	pub dry_run: bool,
+
 	#[command(flatten)]
 	pub apply_args: ApplyArgs,
 }
@@ -205,3 +305,189 @@ pub struct AccentSet {
 	about = "Unset the current accent color. This will cause an error on the next application."
 )]
 pub struct AccentUnset;
+
+#[derive(Parser, Debug)]
+#[command(about = "Switch between the profiles defined in your config")]
+pub struct Profile {
+	#[command(subcommand)]
+	pub command: ProfileCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommand {
+	Switch(ProfileSwitch),
+	List(ProfileList),
+	Current(ProfileCurrent),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Activate a profile, along with its theme and accent color")]
+pub struct ProfileSwitch {
+	#[arg(help = "The name of the profile to switch to")]
+	pub name: String,
+
+	#[command(flatten)]
+	pub update_args: UpdateArgs,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "List the names of the profiles defined in your config")]
+pub struct ProfileList;
+
+#[derive(Parser, Debug)]
+#[command(about = "Print the name of the active profile")]
+pub struct ProfileCurrent;
+
+#[derive(Parser, Debug)]
+#[command(about = "Query or change the configuration in config.toml")]
+pub struct Config {
+	#[command(subcommand)]
+	pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+	Get(ConfigGet),
+	Set(ConfigSet),
+	Unset(ConfigUnset),
+	Edit(ConfigEdit),
+	Path(ConfigPath),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Print the value of a config entry")]
+pub struct ConfigGet {
+	#[arg(help = "The dot-separated path of the entry, like `kitty.background_opacity`")]
+	pub key: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Set a config entry in config.toml, keeping its comments and formatting")]
+pub struct ConfigSet {
+	#[arg(help = "The dot-separated path of the entry, like `kitty.background_opacity`")]
+	pub key: String,
+
+	#[arg(help = "The value to set, in TOML syntax")]
+	pub value: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Remove a config entry from config.toml")]
+pub struct ConfigUnset {
+	#[arg(help = "The dot-separated path of the entry, like `kitty.background_opacity`")]
+	pub key: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Open config.toml in your editor, and check it for errors afterwards")]
+pub struct ConfigEdit;
+
+#[derive(Parser, Debug)]
+#[command(about = "Print the path of the config file")]
+pub struct ConfigPath;
+
+#[derive(Parser, Debug)]
+#[command(about = "List, inspect, enable and disable modules")]
+pub struct Module {
+	#[command(subcommand)]
+	pub command: ModuleCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ModuleCommand {
+	List(ModuleList),
+	Info(ModuleInfo),
+	Enable(ModuleEnable),
+	Disable(ModuleDisable),
+	Path(ModulePath),
+	New(ModuleNew),
+	Test(ModuleTest),
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "List all available modules, marking the ones enabled in your config")]
+pub struct ModuleList;
+
+#[derive(Parser, Debug)]
+#[command(about = "Show the information from a module's manifest")]
+pub struct ModuleInfo {
+	#[arg(help = "The name of the module")]
+	pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Add a module to the `modules` list in config.toml")]
+pub struct ModuleEnable {
+	#[arg(help = "The name of the module to enable")]
+	pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Remove a module from the `modules` list in config.toml")]
+pub struct ModuleDisable {
+	#[arg(help = "The name of the module to disable")]
+	pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Print the path of the directory a module is loaded from")]
+pub struct ModulePath {
+	#[arg(help = "The name of the module")]
+	pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+	about = "Create a new module in ~/.config/niji/modules, with a module.lua, a template, a \
+	         manifest and a test case to start from"
+)]
+pub struct ModuleNew {
+	#[arg(help = "The name of the new module")]
+	pub name: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+	about = "Apply a module in a sandbox, and compare the files it writes and the commands it \
+	         runs to the snapshots in its tests directory"
+)]
+pub struct ModuleTest {
+	#[arg(help = "The name of the module to test")]
+	pub name: String,
+
+	#[arg(
+		short = 't',
+		long = "theme",
+		help = "Only test with the given theme, instead of every built-in theme"
+	)]
+	pub theme: Option<String>,
+
+	#[arg(
+		short = 'u',
+		long = "update",
+		help = "Rewrite the snapshots instead of comparing them"
+	)]
+	pub update: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Show the current theme, accent color and profile")]
+pub struct Status;
+
+#[derive(Parser, Debug)]
+#[command(about = "List previous changes to the theme and accent color")]
+pub struct History;
+
+#[derive(Parser, Debug)]
+#[command(about = "Revert to the previous theme and accent color")]
+pub struct Undo {
+	#[command(flatten)]
+	pub update_args: UpdateArgs,
+}
+
+#[derive(Parser, Debug)]
+#[command(about = "Restore the theme and accent color that were reverted by `niji undo`")]
+pub struct Redo {
+	#[command(flatten)]
+	pub update_args: UpdateArgs,
+}
@@ -1,10 +1,19 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+	collections::HashMap,
+	env, fs,
+	hash::{Hash, Hasher},
+	path::{Path, PathBuf},
+	str::FromStr,
+};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow};
+use mlua::FromLua;
 use niji_macros::IntoLua;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, IntoLua, Serialize, Deserialize)]
+use crate::{hooks::Hooks, utils::fs::find_program};
+
+#[derive(Debug, Default, Clone, PartialEq, IntoLua, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ModuleConfigValue {
 	#[default]
@@ -17,6 +26,63 @@ pub enum ModuleConfigValue {
 	Map(HashMap<String, ModuleConfigValue>),
 }
 
+impl Hash for ModuleConfigValue {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		std::mem::discriminant(self).hash(state);
+		match self {
+			Self::Nil => (),
+			Self::String(value) => value.hash(state),
+			Self::Int(value) => value.hash(state),
+			Self::Float(value) => value.to_bits().hash(state),
+			Self::Bool(value) => value.hash(state),
+			Self::Vec(values) => values.hash(state),
+			Self::Map(map) => hash_map_sorted(map, state),
+		}
+	}
+}
+
+impl FromLua for ModuleConfigValue {
+	fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+		match value {
+			mlua::Value::Nil => Ok(Self::Nil),
+			mlua::Value::Boolean(value) => Ok(Self::Bool(value)),
+			mlua::Value::Integer(value) => Ok(Self::Int(value)),
+			// LuaJIT doesn't distinguish between integers and floats
+			#[allow(clippy::cast_possible_truncation)]
+			mlua::Value::Number(value) if value.fract() == 0.0 && value.abs() < 2f64.powi(53) => {
+				Ok(Self::Int(value as i64))
+			}
+			mlua::Value::Number(value) => Ok(Self::Float(value)),
+			mlua::Value::String(value) => Ok(Self::String(value.to_str()?.to_string())),
+			mlua::Value::Table(table) => {
+				if table.raw_len() != 0 {
+					let values: Vec<Self> = table.sequence_values().collect::<mlua::Result<_>>()?;
+					// Other keys would be lost when converting the table to a list
+					if table.pairs::<mlua::Value, mlua::Value>().count() != values.len() {
+						return Err(mlua::Error::runtime(
+							"Cannot use a table with both list entries and other keys as a config \
+							 value",
+						));
+					}
+					return Ok(Self::Vec(values));
+				}
+				Ok(Self::Map(table.pairs().collect::<mlua::Result<_>>()?))
+			}
+			_ => Err(mlua::Error::runtime(format!(
+				"Cannot use a {} as a config value",
+				value.type_name()
+			))),
+		}
+	}
+}
+
+/// Hashes a map independently of its unspecified iteration order.
+pub fn hash_map_sorted<V: Hash, H: Hasher>(map: &HashMap<String, V>, state: &mut H) {
+	let mut entries: Vec<_> = map.iter().collect();
+	entries.sort_unstable_by_key(|(key, _)| *key);
+	entries.hash(state);
+}
+
 pub type ModuleConfig = HashMap<String, ModuleConfigValue>;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -41,9 +107,113 @@ impl DisableReloads {
 	}
 }
 
+/// Conditions that all have to be met for a module to be activated.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModuleCondition {
+	pub env: HashMap<String, String>,
+	pub program: Option<String>,
+	pub hostname: Option<String>,
+}
+
+impl ModuleCondition {
+	fn unmet_reason(&self) -> Option<String> {
+		let mut env: Vec<_> = self.env.iter().collect();
+		env.sort_unstable();
+		for (name, expected) in env {
+			if env::var(name).ok().as_ref() != Some(expected) {
+				return Some(format!("${name} is not \"{expected}\""));
+			}
+		}
+
+		if let Some(program) = &self.program
+			&& find_program(program).is_none()
+		{
+			return Some(format!("{program} is not installed"));
+		}
+
+		if let Some(hostname) = &self.hostname
+			&& get_hostname().as_ref() != Some(hostname)
+		{
+			return Some(format!("the hostname is not \"{hostname}\""));
+		}
+
+		None
+	}
+}
+
+fn get_hostname() -> Option<String> {
+	["/proc/sys/kernel/hostname", "/etc/hostname"]
+		.into_iter()
+		.find_map(|path| fs::read_to_string(path).ok())
+		.map(|hostname| hostname.trim().to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModuleEntry {
+	Name(String),
+	Conditional { name: String, when: ModuleCondition },
+}
+
+impl ModuleEntry {
+	pub fn name(&self) -> &str {
+		match self {
+			Self::Name(name) | Self::Conditional { name, .. } => name,
+		}
+	}
+
+	/// Returns whether the module should be activated, logging the reason if
+	/// not.
+	pub fn is_active(&self) -> bool {
+		let Self::Conditional { name, when } = self else {
+			return true;
+		};
+		let Some(reason) = when.unmet_reason() else {
+			return true;
+		};
+		log::debug!("Skipping module {name}, since {reason}");
+		false
+	}
+}
+
+/// The modules a module needs to be applied before or after.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModuleOrder {
+	pub before: Option<Vec<String>>,
+	pub after: Option<Vec<String>>,
+}
+
+impl ModuleOrder {
+	pub fn override_with(&mut self, other: ModuleOrder) {
+		if other.before.is_some() {
+			self.before = other.before;
+		}
+		if other.after.is_some() {
+			self.after = other.after;
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+	pub theme: Option<String>,
+
+	pub accent: Option<String>,
+
+	pub modules: Option<Vec<ModuleEntry>>,
+
+	pub global: ModuleConfig,
+
+	#[serde(flatten)]
+	pub module_config: HashMap<String, ModuleConfig>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-	pub modules: Vec<String>,
+	pub modules: Vec<ModuleEntry>,
 
 	#[serde(default)]
 	pub disable_reloads: DisableReloads,
@@ -51,6 +221,15 @@ pub struct Config {
 	#[serde(default)]
 	pub global: ModuleConfig,
 
+	#[serde(default)]
+	pub module_order: HashMap<String, ModuleOrder>,
+
+	#[serde(default)]
+	pub profiles: HashMap<String, Profile>,
+
+	#[serde(default)]
+	pub hooks: Hooks,
+
 	#[serde(flatten)]
 	#[allow(clippy::struct_field_names)]
 	pub module_config: HashMap<String, ModuleConfig>,
@@ -62,17 +241,690 @@ impl Default for Config {
 			modules: vec![],
 			disable_reloads: DisableReloads::None,
 			global: ModuleConfig::new(),
+			module_order: HashMap::new(),
+			profiles: HashMap::new(),
+			hooks: Hooks::default(),
 			module_config: HashMap::new(),
 		}
 	}
 }
 
-pub fn read_config(path: impl AsRef<Path>) -> anyhow::Result<Config> {
-	if !path.as_ref().exists() {
+impl Config {
+	pub fn get_profile(&self, name: &str) -> anyhow::Result<&Profile> {
+		self.profiles
+			.get(name)
+			.ok_or_else(|| anyhow!("Profile '{name}' doesn't exist"))
+	}
+
+	pub fn with_profile(&self, name: &str) -> anyhow::Result<Config> {
+		let profile = self.get_profile(name)?;
+		let mut config = self.clone();
+
+		if let Some(modules) = &profile.modules {
+			config.modules.clone_from(modules);
+		}
+		merge_module_config(&mut config.global, profile.global.clone());
+		for (module, overrides) in &profile.module_config {
+			merge_module_config(
+				config.module_config.entry(module.clone()).or_default(),
+				overrides.clone(),
+			);
+		}
+
+		Ok(config)
+	}
+
+	/// Returns the names of the modules whose activation conditions are met.
+	pub fn active_modules(&self) -> Vec<String> {
+		self.modules
+			.iter()
+			.filter(|entry| entry.is_active())
+			.map(|entry| entry.name().to_string())
+			.collect()
+	}
+
+	/// Builds the config for the given module by merging its module-specific
+	/// config over the global config.
+	pub fn for_module(&self, name: &str) -> ModuleConfig {
+		let mut module_config = ModuleConfig::new();
+		merge_module_config(&mut module_config, self.global.clone());
+		if let Some(specific) = self.module_config.get(name) {
+			merge_module_config(&mut module_config, specific.clone());
+		}
+		module_config
+	}
+}
+
+/// Recursively merges `overlay` into `base`. Maps are merged key by key, and
+/// all other values are replaced. A key ending in `!` replaces the value
+/// instead of merging into it, and a key ending in `+` appends to a list.
+pub fn merge_module_config(base: &mut ModuleConfig, overlay: ModuleConfig) {
+	// Keys with markers are merged after the plain keys, so that the result
+	// doesn't depend on the order of the map if it contains both
+	let mut entries: Vec<_> = overlay.into_iter().collect();
+	entries.sort_by_key(|(key, _)| {
+		if key.ends_with('!') {
+			1
+		} else if key.ends_with('+') {
+			2
+		} else {
+			0
+		}
+	});
+
+	for (key, value) in entries {
+		let value = normalize_module_config_value(value);
+		if let Some(key) = key.strip_suffix('!') {
+			base.insert(key.to_string(), value);
+		} else if let Some(key) = key.strip_suffix('+') {
+			match (base.get_mut(key), value) {
+				(Some(ModuleConfigValue::Vec(list)), ModuleConfigValue::Vec(values)) => {
+					list.extend(values);
+				}
+				(Some(ModuleConfigValue::Vec(list)), value) => list.push(value),
+				(_, value) => {
+					base.insert(key.to_string(), value);
+				}
+			}
+		} else {
+			match (base.get_mut(&key), value) {
+				(Some(ModuleConfigValue::Map(base_map)), ModuleConfigValue::Map(map)) => {
+					merge_module_config(base_map, map);
+				}
+				(_, value) => {
+					base.insert(key, value);
+				}
+			}
+		}
+	}
+}
+
+/// Removes the `!` and `+` merge markers from the keys of nested maps.
+fn normalize_module_config_value(value: ModuleConfigValue) -> ModuleConfigValue {
+	match value {
+		ModuleConfigValue::Map(map) => {
+			let mut normalized = ModuleConfig::new();
+			merge_module_config(&mut normalized, map);
+			ModuleConfigValue::Map(normalized)
+		}
+		ModuleConfigValue::Vec(values) => ModuleConfigValue::Vec(
+			values
+				.into_iter()
+				.map(normalize_module_config_value)
+				.collect(),
+		),
+		value => value,
+	}
+}
+
+pub const KEY_SEPARATOR: &str = ".";
+const DROPIN_DIR: &str = "config.d";
+const INCLUDE_KEY: &str = "include";
+const MODULES_KEY: &str = "modules";
+const MODULES_EXTRA_KEY: &str = "modules_extra";
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Splits a config key like `kitty.background_opacity` into its components.
+pub fn parse_key_path(key: &str, separator: &str) -> anyhow::Result<Vec<String>> {
+	let path: Vec<String> = key.split(separator).map(str::to_string).collect();
+	if path.iter().any(String::is_empty) {
+		return Err(anyhow!("Invalid config key \"{key}\""));
+	}
+	Ok(path)
+}
+
+/// An override of a single config entry, such as `kitty.background_opacity=0.9`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+	pub path: Vec<String>,
+	pub value: toml::Value,
+}
+
+impl ConfigOverride {
+	const ENV_PREFIX: &'static str = "NIJI_CONFIG__";
+
+	fn new(key: &str, separator: &str, value: &str) -> anyhow::Result<Self> {
+		let path = parse_key_path(key, separator)?;
+
+		// Values that aren't valid TOML are treated as plain strings
+		let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+			.ok()
+			.and_then(|mut table| table.remove("value"))
+			.unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+		Ok(Self { path, value })
+	}
+
+	/// Collects the overrides set via `NIJI_CONFIG__<KEY>__<KEY>` environment
+	/// variables.
+	pub fn from_env() -> anyhow::Result<Vec<Self>> {
+		let mut overrides = Vec::new();
+		for (name, value) in env::vars() {
+			let Some(key) = name.strip_prefix(Self::ENV_PREFIX) else {
+				continue;
+			};
+			overrides.push(
+				Self::new(&key.to_lowercase(), "__", &value)
+					.with_context(|| format!("Invalid environment variable {name}"))?,
+			);
+		}
+		overrides.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+		Ok(overrides)
+	}
+
+	fn apply(&self, table: &mut toml::Table) -> anyhow::Result<()> {
+		let (last, parents) = self.path.split_last().unwrap();
+		let mut table = table;
+		for key in parents {
+			let value = table
+				.entry(key.clone())
+				.or_insert_with(|| toml::Value::Table(toml::Table::new()));
+			let toml::Value::Table(inner) = value else {
+				return Err(anyhow!(
+					"Cannot override {}, since {key} is not a table",
+					self.path.join(".")
+				));
+			};
+			table = inner;
+		}
+		table.insert(last.clone(), self.value.clone());
+		Ok(())
+	}
+}
+
+impl FromStr for ConfigOverride {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let Some((key, value)) = s.split_once('=') else {
+			return Err(anyhow!(
+				"Invalid config override \"{s}\"; expected the format key.path=value"
+			));
+		};
+		Self::new(key.trim(), KEY_SEPARATOR, value.trim())
+	}
+}
+
+/// Reads the config file at `path`, along with the files it includes and the
+/// drop-in files in the `config.d` directory next to it, and applies the given
+/// overrides on top.
+pub fn read_config(path: impl AsRef<Path>, overrides: &[ConfigOverride]) -> anyhow::Result<Config> {
+	let path = path.as_ref();
+	let source = if path.exists() {
+		Some(read_config_file(path)?)
+	} else {
+		None
+	};
+	build_config(path, source.as_deref(), overrides)
+}
+
+/// Reads the config like [`read_config`], but uses `source` as the contents
+/// of the config file at `path`, so that changes can be checked before they
+/// are written.
+pub fn read_config_str(
+	path: impl AsRef<Path>,
+	source: &str,
+	overrides: &[ConfigOverride],
+) -> anyhow::Result<Config> {
+	build_config(path.as_ref(), Some(source), overrides)
+}
+
+fn build_config(
+	path: &Path,
+	source: Option<&str>,
+	overrides: &[ConfigOverride],
+) -> anyhow::Result<Config> {
+	let dropins = find_dropins(path)?;
+	if source.is_none() && dropins.is_empty() && overrides.is_empty() {
 		return Ok(Config::default());
 	}
-	let config_str = fs::read_to_string(&path)?;
-	let config: Config = toml::from_str(&config_str)
-		.with_context(|| format!("Syntax error in {}", path.as_ref().display()))?;
-	Ok(config)
+
+	let mut table = toml::Table::new();
+	match source {
+		Some(source) => merge_config_tables(&mut table, parse_config_table(path, source, 0)?),
+		None if dropins.is_empty() => {
+			table.insert(MODULES_KEY.to_string(), toml::Value::Array(vec![]));
+		}
+		None => (),
+	}
+	for file in dropins {
+		merge_config_tables(&mut table, read_config_table(&file, 0)?);
+	}
+	for config_override in overrides {
+		config_override.apply(&mut table)?;
+	}
+
+	if let Some(extra) = table.remove(MODULES_EXTRA_KEY) {
+		let modules = table
+			.entry(MODULES_KEY)
+			.or_insert_with(|| toml::Value::Array(vec![]));
+		if let (toml::Value::Array(modules), toml::Value::Array(extra)) = (modules, extra) {
+			for module in extra {
+				if !modules.contains(&module) {
+					modules.push(module);
+				}
+			}
+		}
+	}
+
+	toml::Value::Table(table)
+		.try_into()
+		.with_context(|| format!("Invalid config in {}", path.display()))
+}
+
+fn find_dropins(config_file: &Path) -> anyhow::Result<Vec<PathBuf>> {
+	let Some(dropin_dir) = config_file.parent().map(|dir| dir.join(DROPIN_DIR)) else {
+		return Ok(vec![]);
+	};
+	if !dropin_dir.is_dir() {
+		return Ok(vec![]);
+	}
+
+	let mut dropins = fs::read_dir(&dropin_dir)
+		.with_context(|| format!("Failed to read {}", dropin_dir.display()))?
+		.map(|entry| entry.map(|e| e.path()))
+		.filter(|path| {
+			path.as_ref().map_or(true, |p| {
+				p.is_file() && p.extension() == Some("toml".as_ref())
+			})
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	dropins.sort_unstable();
+	Ok(dropins)
+}
+
+/// Reads a single config file, with the files it includes merged underneath it.
+fn read_config_table(path: &Path, depth: usize) -> anyhow::Result<toml::Table> {
+	if depth > MAX_INCLUDE_DEPTH {
+		return Err(anyhow!(
+			"Config files are included too deeply; is there an include cycle involving {}?",
+			path.display()
+		));
+	}
+
+	parse_config_table(path, &read_config_file(path)?, depth)
+}
+
+fn read_config_file(path: &Path) -> anyhow::Result<String> {
+	fs::read_to_string(path)
+		.with_context(|| format!("Failed to read config file {}", path.display()))
+}
+
+/// Parses the contents of the config file at `path`, with the files it
+/// includes merged underneath it.
+fn parse_config_table(path: &Path, source: &str, depth: usize) -> anyhow::Result<toml::Table> {
+	let mut own_table: toml::Table =
+		toml::from_str(source).with_context(|| format!("Syntax error in {}", path.display()))?;
+
+	let includes = match own_table.remove(INCLUDE_KEY) {
+		None => vec![],
+		Some(toml::Value::String(include)) => vec![include],
+		Some(toml::Value::Array(includes)) => includes
+			.into_iter()
+			.map(|include| match include {
+				toml::Value::String(include) => Ok(include),
+				_ => Err(anyhow!("`include` must only contain paths")),
+			})
+			.collect::<anyhow::Result<_>>()?,
+		Some(_) => {
+			return Err(anyhow!(
+				"`include` in {} must be a list of paths",
+				path.display()
+			));
+		}
+	};
+
+	let mut table = toml::Table::new();
+	for include in includes {
+		let include_path = PathBuf::from(&*shellexpand::tilde(&include));
+		let include_path = match path.parent() {
+			Some(dir) => dir.join(include_path),
+			None => include_path,
+		};
+		let include_table = read_config_table(&include_path, depth + 1)
+			.with_context(|| format!("Failed to include {include} from {}", path.display()))?;
+		merge_config_tables(&mut table, include_table);
+	}
+	merge_config_tables(&mut table, own_table);
+
+	Ok(table)
+}
+
+fn merge_config_tables(base: &mut toml::Table, overlay: toml::Table) {
+	for (key, value) in overlay {
+		match (base.get_mut(&key), value) {
+			(Some(toml::Value::Array(base_extra)), toml::Value::Array(extra))
+				if key == MODULES_EXTRA_KEY =>
+			{
+				base_extra.extend(extra);
+			}
+			(Some(base_value), value) => merge_values(base_value, value),
+			(None, value) => {
+				base.insert(key, value);
+			}
+		}
+	}
+}
+
+fn merge_values(base: &mut toml::Value, overlay: toml::Value) {
+	match (base, overlay) {
+		(toml::Value::Table(base), toml::Value::Table(overlay)) => {
+			for (key, value) in overlay {
+				match base.get_mut(&key) {
+					Some(base_value) => merge_values(base_value, value),
+					None => {
+						base.insert(key, value);
+					}
+				}
+			}
+		}
+		(base, overlay) => *base = overlay,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	const PROFILES_CONFIG: &str = r#"
+		modules = ["gtk", "kitty"]
+
+		[global]
+		font_family = "Fira Sans"
+		font_scale = 1.0
+
+		[kitty]
+		font_family = "Fira Code"
+
+		[profiles.presentation]
+		theme = "gruvbox-light"
+		accent = "blue"
+		modules = ["gtk"]
+
+		[profiles.presentation.global]
+		font_scale = 1.5
+
+		[profiles.presentation.gtk]
+		cursor_size = 48
+	"#;
+
+	#[test]
+	fn read_dropins_and_includes() {
+		let tempdir = tempdir().unwrap();
+		let config_dir = tempdir.path().join("niji");
+		fs::create_dir_all(config_dir.join("config.d")).unwrap();
+		fs::write(
+			tempdir.path().join("shared.toml"),
+			"modules = [\"gtk\"]\n[global]\nfont_family = \"Fira Sans\"\nfont_scale = 1.0",
+		)
+		.unwrap();
+		fs::write(
+			config_dir.join("config.toml"),
+			"include = [\"../shared.toml\"]\n[global]\nfont_scale = 1.2\n[kitty]\nfont_family = \
+			 \"Fira Code\"",
+		)
+		.unwrap();
+		fs::write(
+			config_dir.join("config.d/10-local.toml"),
+			"modules_extra = [\"kitty\"]\n[kitty]\nbackground_opacity = 0.9",
+		)
+		.unwrap();
+		fs::write(
+			config_dir.join("config.d/20-local.toml"),
+			"modules_extra = [\"waybar\"]\n[global]\nfont_scale = 1.5",
+		)
+		.unwrap();
+		fs::write(config_dir.join("config.d/ignored.txt"), "not toml").unwrap();
+
+		let config = read_config(config_dir.join("config.toml"), &[]).unwrap();
+		assert_eq!(config.active_modules(), vec!["gtk", "kitty", "waybar"]);
+		assert!(matches!(
+			config.global.get("font_scale"),
+			Some(ModuleConfigValue::Float(1.5))
+		));
+		assert!(matches!(
+			config.global.get("font_family"),
+			Some(ModuleConfigValue::String(s)) if s == "Fira Sans"
+		));
+		assert_eq!(config.module_config["kitty"].len(), 2);
+		assert!(!config.module_config.contains_key("include"));
+		assert!(!config.module_config.contains_key("modules_extra"));
+	}
+
+	#[test]
+	fn include_cycle() {
+		let tempdir = tempdir().unwrap();
+		fs::write(
+			tempdir.path().join("config.toml"),
+			"include = \"config.toml\"\nmodules = []",
+		)
+		.unwrap();
+
+		read_config(tempdir.path().join("config.toml"), &[]).unwrap_err();
+	}
+
+	#[test]
+	fn parse_override() {
+		assert_eq!(
+			"kitty.background_opacity=0.9"
+				.parse::<ConfigOverride>()
+				.unwrap(),
+			ConfigOverride {
+				path: vec!["kitty".to_string(), "background_opacity".to_string()],
+				value: toml::Value::Float(0.9)
+			}
+		);
+		assert_eq!(
+			"modules=[\"gtk\"]".parse::<ConfigOverride>().unwrap().value,
+			toml::Value::Array(vec![toml::Value::String("gtk".to_string())])
+		);
+		assert_eq!(
+			"global.font_family=Fira Sans"
+				.parse::<ConfigOverride>()
+				.unwrap()
+				.value,
+			toml::Value::String("Fira Sans".to_string())
+		);
+		"kitty.background_opacity"
+			.parse::<ConfigOverride>()
+			.unwrap_err();
+		"kitty..opacity=1".parse::<ConfigOverride>().unwrap_err();
+	}
+
+	#[test]
+	fn apply_overrides() {
+		let tempdir = tempdir().unwrap();
+		fs::write(
+			tempdir.path().join("config.toml"),
+			"modules = []\n[kitty]\nbackground_opacity = 1.0\nfont_family = \"Fira Code\"",
+		)
+		.unwrap();
+
+		let config = read_config(
+			tempdir.path().join("config.toml"),
+			&[
+				"kitty.background_opacity=0.9".parse().unwrap(),
+				"global.wallpaper.default=\"wp.png\"".parse().unwrap(),
+			],
+		)
+		.unwrap();
+		assert!(matches!(
+			config.module_config["kitty"].get("background_opacity"),
+			Some(ModuleConfigValue::Float(0.9))
+		));
+		assert!(config.module_config["kitty"].contains_key("font_family"));
+		assert!(matches!(
+			config.global.get("wallpaper"),
+			Some(ModuleConfigValue::Map(map)) if map.contains_key("default")
+		));
+
+		read_config(
+			tempdir.path().join("config.toml"),
+			&["kitty.background_opacity.nested=1".parse().unwrap()],
+		)
+		.unwrap_err();
+	}
+
+	#[test]
+	fn merge_nested_module_config() {
+		let config: Config = toml::from_str(
+			r#"
+			modules = []
+
+			[global]
+			swaybar = { bar_id = 0, position = "top" }
+			wallpaper = { default = "wp1.png", dracula = "wp2.png" }
+			custom_modules = ["custom-gpu"]
+
+			[waybar]
+			swaybar = { bar_id = 1 }
+			"wallpaper!" = { default = "wp3.png" }
+			"custom_modules+" = ["custom-cpu"]
+			"#,
+		)
+		.unwrap();
+
+		let module_config = config.for_module("waybar");
+		let ModuleConfigValue::Map(swaybar) = &module_config["swaybar"] else {
+			panic!("swaybar is not a map");
+		};
+		assert_eq!(swaybar["bar_id"], ModuleConfigValue::Int(1));
+		assert_eq!(
+			swaybar["position"],
+			ModuleConfigValue::String("top".to_string())
+		);
+
+		let ModuleConfigValue::Map(wallpaper) = &module_config["wallpaper"] else {
+			panic!("wallpaper is not a map");
+		};
+		assert_eq!(wallpaper.len(), 1);
+
+		assert_eq!(
+			module_config["custom_modules"],
+			ModuleConfigValue::Vec(vec![
+				ModuleConfigValue::String("custom-gpu".to_string()),
+				ModuleConfigValue::String("custom-cpu".to_string()),
+			])
+		);
+		assert!(!module_config.contains_key("wallpaper!"));
+		assert!(!module_config.contains_key("custom_modules+"));
+
+		let ModuleConfigValue::Map(swaybar) = &config.for_module("kitty")["swaybar"] else {
+			panic!("swaybar is not a map");
+		};
+		assert_eq!(swaybar["bar_id"], ModuleConfigValue::Int(0));
+	}
+
+	#[test]
+	fn module_config_value_from_lua() {
+		let lua = mlua::Lua::new();
+		let convert = |source: &str| lua.load(source).eval::<ModuleConfigValue>();
+
+		assert_eq!(
+			convert("{ 1, 2 }").unwrap(),
+			ModuleConfigValue::Vec(vec![ModuleConfigValue::Int(1), ModuleConfigValue::Int(2)])
+		);
+		assert_eq!(
+			convert("{ a = true }").unwrap(),
+			ModuleConfigValue::Map(HashMap::from([(
+				"a".to_string(),
+				ModuleConfigValue::Bool(true)
+			)]))
+		);
+		convert("{ 1, 2, a = true }").unwrap_err();
+	}
+
+	#[test]
+	fn merge_markers_with_plain_keys() {
+		// A drop-in that appends to a list is merged into the same table as
+		// the list itself. Each parsed map has a different iteration order.
+		for _ in 0..16 {
+			let overlay: ModuleConfig = toml::from_str(
+				r#"
+				colors = ["red"]
+				"colors+" = ["blue"]
+				"fonts+" = ["Fira Sans"]
+				"fonts!" = ["Inter"]
+				fonts = ["Noto Sans"]
+				"#,
+			)
+			.unwrap();
+			let mut module_config = ModuleConfig::new();
+			merge_module_config(&mut module_config, overlay);
+			assert_eq!(
+				module_config["colors"],
+				ModuleConfigValue::Vec(vec![
+					ModuleConfigValue::String("red".to_string()),
+					ModuleConfigValue::String("blue".to_string()),
+				])
+			);
+			assert_eq!(
+				module_config["fonts"],
+				ModuleConfigValue::Vec(vec![
+					ModuleConfigValue::String("Inter".to_string()),
+					ModuleConfigValue::String("Fira Sans".to_string()),
+				])
+			);
+		}
+	}
+
+	#[test]
+	fn conditional_modules() {
+		let config: Config = toml::from_str(
+			r#"
+			modules = [
+				"gtk",
+				{ name = "kitty", when = { program = "sh" } },
+				{ name = "sway", when = { program = "niji-nonexistent-program" } },
+				{ name = "waybar", when = { env = { NIJI_TEST_NONEXISTENT_VAR = "sway" } } },
+				{ name = "mako", when = { hostname = "niji-nonexistent-host" } },
+			]
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(config.active_modules(), vec!["gtk", "kitty"]);
+
+		toml::from_str::<Config>("modules = [{ name = \"gtk\", when = { os = \"linux\" } }]")
+			.unwrap_err();
+	}
+
+	#[test]
+	fn parse_profiles() {
+		let config: Config = toml::from_str(PROFILES_CONFIG).unwrap();
+
+		assert!(!config.module_config.contains_key("profiles"));
+		let profile = config.get_profile("presentation").unwrap();
+		assert_eq!(profile.theme.as_deref(), Some("gruvbox-light"));
+		assert_eq!(profile.accent.as_deref(), Some("blue"));
+		assert_eq!(
+			profile.modules,
+			Some(vec![ModuleEntry::Name("gtk".to_string())])
+		);
+		assert!(profile.module_config.contains_key("gtk"));
+		config.get_profile("work").unwrap_err();
+	}
+
+	#[test]
+	fn merge_profile() {
+		let config: Config = toml::from_str(PROFILES_CONFIG).unwrap();
+		let merged = config.with_profile("presentation").unwrap();
+
+		assert_eq!(merged.active_modules(), vec!["gtk".to_string()]);
+		assert!(matches!(
+			merged.global.get("font_scale"),
+			Some(ModuleConfigValue::Float(1.5))
+		));
+		assert!(matches!(
+			merged.global.get("font_family"),
+			Some(ModuleConfigValue::String(s)) if s == "Fira Sans"
+		));
+		assert!(matches!(
+			merged.module_config["gtk"].get("cursor_size"),
+			Some(ModuleConfigValue::Int(48))
+		));
+		assert!(merged.module_config.contains_key("kitty"));
+	}
 }
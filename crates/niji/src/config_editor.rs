@@ -0,0 +1,324 @@
+use std::{
+	env, fs,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::{Context, anyhow};
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+use crate::{config, utils::fs::write_atomic};
+
+/// Edits a config file in place, preserving its comments and formatting.
+#[derive(Debug)]
+pub struct ConfigEditor {
+	path: PathBuf,
+	document: DocumentMut,
+}
+
+impl ConfigEditor {
+	pub fn open(path: &Path) -> anyhow::Result<Self> {
+		let source = if path.exists() {
+			fs::read_to_string(path)
+				.with_context(|| format!("Failed to read {}", path.display()))?
+		} else {
+			String::new()
+		};
+		let document = source
+			.parse()
+			.with_context(|| format!("Syntax error in {}", path.display()))?;
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			document,
+		})
+	}
+
+	/// Sets the entry at `key` to `value`, which uses TOML syntax. Values that
+	/// aren't valid TOML are stored as plain strings.
+	pub fn set(&mut self, key: &[String], value: &str) -> anyhow::Result<()> {
+		let (last, parents) = key
+			.split_last()
+			.ok_or_else(|| anyhow!("Config key must not be empty"))?;
+
+		let mut table = self.document.as_table_mut() as &mut dyn toml_edit::TableLike;
+		for parent in parents {
+			let item = table.entry(parent).or_insert_with(|| {
+				let mut table = Table::new();
+				table.set_implicit(true);
+				Item::Table(table)
+			});
+			table = item.as_table_like_mut().ok_or_else(|| {
+				anyhow!(
+					"Cannot set {}, since {parent} is not a table",
+					key.join(config::KEY_SEPARATOR)
+				)
+			})?;
+		}
+
+		let mut value = value.parse::<Value>().unwrap_or_else(|_| value.into());
+		// Replace existing values in place, so that surrounding comments are kept
+		if let Some(existing) = table.get_mut(last).and_then(Item::as_value_mut) {
+			*value.decor_mut() = existing.decor().clone();
+			*existing = value;
+		} else {
+			table.insert(last, Item::Value(value));
+		}
+		Ok(())
+	}
+
+	pub fn unset(&mut self, key: &[String]) -> anyhow::Result<()> {
+		let (last, parents) = key
+			.split_last()
+			.ok_or_else(|| anyhow!("Config key must not be empty"))?;
+
+		let mut table = self.document.as_table_mut() as &mut dyn toml_edit::TableLike;
+		for parent in parents {
+			table = table
+				.get_mut(parent)
+				.and_then(Item::as_table_like_mut)
+				.ok_or_else(|| anyhow!("{} is not set", key.join(config::KEY_SEPARATOR)))?;
+		}
+		if table.remove(last).is_none() {
+			return Err(anyhow!("{} is not set", key.join(config::KEY_SEPARATOR)));
+		}
+		Ok(())
+	}
+
+	/// Adds a module to the `modules` list. Returns false if the module is
+	/// already in the list.
+	pub fn enable_module(&mut self, name: &str) -> anyhow::Result<bool> {
+		let modules = self
+			.document
+			.entry("modules")
+			.or_insert_with(|| toml_edit::value(Array::new()));
+
+		match modules {
+			Item::Value(Value::Array(array)) => {
+				if array
+					.iter()
+					.any(|entry| module_entry_name(entry) == Some(name))
+				{
+					return Ok(false);
+				}
+				array.push(name);
+			}
+			Item::ArrayOfTables(array) => {
+				if array
+					.iter()
+					.any(|table| table.get("name").and_then(Item::as_str) == Some(name))
+				{
+					return Ok(false);
+				}
+				let mut table = Table::new();
+				table.insert("name", toml_edit::value(name));
+				array.push(table);
+			}
+			_ => return Err(anyhow!("`modules` is not a list")),
+		}
+		Ok(true)
+	}
+
+	/// Removes all entries for a module from the `modules` list, including
+	/// conditional ones. Returns false if the module isn't in the list.
+	pub fn disable_module(&mut self, name: &str) -> anyhow::Result<bool> {
+		let Some(modules) = self.document.get_mut("modules") else {
+			return Ok(false);
+		};
+
+		match modules {
+			Item::Value(Value::Array(array)) => {
+				let len = array.len();
+				// Keep the formatting in front of the first entry if it is removed
+				let first_prefix = array
+					.get(0)
+					.and_then(|entry| entry.decor().prefix())
+					.cloned();
+				array.retain(|entry| module_entry_name(entry) != Some(name));
+				if let (Some(first), Some(prefix)) = (array.get_mut(0), first_prefix) {
+					first.decor_mut().set_prefix(prefix);
+				}
+				Ok(array.len() != len)
+			}
+			Item::ArrayOfTables(array) => {
+				let len = array.len();
+				array.retain(|table| table.get("name").and_then(Item::as_str) != Some(name));
+				Ok(array.len() != len)
+			}
+			_ => Err(anyhow!("`modules` is not a list")),
+		}
+	}
+
+	/// Writes the changes to the config file, unless they result in an invalid
+	/// config.
+	pub fn save(&self) -> anyhow::Result<()> {
+		let source = self.document.to_string();
+		config::read_config_str(&self.path, &source, &[])
+			.context("The change would result in an invalid config")?;
+
+		write_atomic(&self.path, source)
+			.with_context(|| format!("Failed to write {}", self.path.display()))
+	}
+}
+
+/// Entries of the `modules` list are either module names, or tables with a
+/// `name` key for conditional modules.
+fn module_entry_name(entry: &Value) -> Option<&str> {
+	entry.as_str().or_else(|| {
+		entry
+			.as_inline_table()
+			.and_then(|table| table.get("name"))
+			.and_then(Value::as_str)
+	})
+}
+
+/// Opens the given file in the user's preferred editor, and waits for it to
+/// be closed.
+pub fn open_in_editor(path: &Path) -> anyhow::Result<()> {
+	let editor = env::var("VISUAL")
+		.or_else(|_| env::var("EDITOR"))
+		.unwrap_or_else(|_| "vi".to_string());
+
+	// The editor command is passed through the shell, so that it may contain
+	// arguments, like `code --wait`.
+	let status = Command::new("sh")
+		.arg("-c")
+		.arg(format!("{editor} \"$1\""))
+		.arg("sh")
+		.arg(path)
+		.status()
+		.with_context(|| format!("Failed to run editor `{editor}`"))?;
+
+	if !status.success() {
+		return Err(anyhow!("Editor `{editor}` exited with {status}"));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	const CONFIG: &str = r#"# My niji config
+modules = ["kitty"] # the modules
+
+[kitty]
+# Make it a bit transparent
+background_opacity = 0.8 # for now
+"#;
+
+	fn key(key: &str) -> Vec<String> {
+		config::parse_key_path(key, config::KEY_SEPARATOR).unwrap()
+	}
+
+	#[test]
+	fn set_preserves_formatting() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("config.toml");
+		fs::write(&path, CONFIG).unwrap();
+
+		let mut editor = ConfigEditor::open(&path).unwrap();
+		editor.set(&key("kitty.background_opacity"), "0.9").unwrap();
+		editor.set(&key("global.font_family"), "Fira Sans").unwrap();
+		editor.set(&key("waybar.swaybar.bar_id"), "1").unwrap();
+		editor.save().unwrap();
+
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			r#"# My niji config
+modules = ["kitty"] # the modules
+
+[kitty]
+# Make it a bit transparent
+background_opacity = 0.9 # for now
+
+[global]
+font_family = "Fira Sans"
+
+[waybar.swaybar]
+bar_id = 1
+"#
+		);
+	}
+
+	#[test]
+	fn unset() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("config.toml");
+		fs::write(&path, CONFIG).unwrap();
+
+		let mut editor = ConfigEditor::open(&path).unwrap();
+		editor.unset(&key("kitty.background_opacity")).unwrap();
+		editor.unset(&key("kitty.font_family")).unwrap_err();
+		editor.unset(&key("waybar.font_family")).unwrap_err();
+		editor.save().unwrap();
+
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"# My niji config\nmodules = [\"kitty\"] # the modules\n\n[kitty]\n"
+		);
+	}
+
+	#[test]
+	fn enable_disable_modules() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("config.toml");
+		fs::write(
+			&path,
+			"modules = [\"kitty\", { name = \"waybar\", when = { env = \"WAYLAND_DISPLAY\" } }]\n",
+		)
+		.unwrap();
+
+		let mut editor = ConfigEditor::open(&path).unwrap();
+		assert!(editor.enable_module("gtk").unwrap());
+		assert!(!editor.enable_module("kitty").unwrap());
+		assert!(!editor.enable_module("waybar").unwrap());
+		assert!(editor.disable_module("waybar").unwrap());
+		assert!(!editor.disable_module("sway").unwrap());
+		editor.save().unwrap();
+
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"modules = [\"kitty\", \"gtk\"]\n"
+		);
+	}
+
+	#[test]
+	fn enable_module_without_list() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("config.toml");
+		fs::write(&path, "[kitty]\nbackground_opacity = 0.8\n").unwrap();
+
+		let mut editor = ConfigEditor::open(&path).unwrap();
+		assert!(!editor.disable_module("kitty").unwrap());
+		assert!(editor.enable_module("kitty").unwrap());
+		editor.save().unwrap();
+
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"modules = [\"kitty\"]\n[kitty]\nbackground_opacity = 0.8\n"
+		);
+	}
+
+	#[test]
+	fn reject_invalid_config() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("config.toml");
+		fs::write(&path, CONFIG).unwrap();
+
+		let mut editor = ConfigEditor::open(&path).unwrap();
+		editor.set(&key("modules"), "42").unwrap();
+		editor.save().unwrap_err();
+
+		assert_eq!(fs::read_to_string(&path).unwrap(), CONFIG);
+
+		// The config is validated before anything is written
+		let new_path = tempdir.path().join("new.toml");
+		let mut editor = ConfigEditor::open(&new_path).unwrap();
+		editor.set(&key("modules"), "42").unwrap();
+		editor.save().unwrap_err();
+		assert!(!new_path.exists());
+	}
+}
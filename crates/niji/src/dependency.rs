@@ -0,0 +1,323 @@
+use std::{cmp::Ordering, fmt, process::Command, str::FromStr};
+
+use anyhow::anyhow;
+use log::debug;
+use serde::Deserialize;
+use serde_with::DeserializeFromStr;
+
+use crate::utils::fs::find_program;
+
+/// A version number made up of dot-separated numeric components, like
+/// `0.30.1`. Missing components compare as zero.
+#[derive(Debug, Clone, DeserializeFromStr)]
+pub struct Version(Vec<u64>);
+
+impl Version {
+	/// Finds the first version number in the output of a `--version` probe.
+	/// Version numbers with more than one component are preferred, so that
+	/// program names like `qt5ct` are skipped.
+	pub fn find_in(output: &str) -> Option<Self> {
+		let candidates: Vec<Self> = output
+			.split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '-' | '+'))
+			.filter_map(|word| {
+				word.trim_start_matches(['v', 'V'])
+					.trim_end_matches(['.', ':'])
+					.parse()
+					.ok()
+			})
+			.collect();
+
+		candidates
+			.iter()
+			.find(|version| version.0.len() > 1)
+			.or(candidates.first())
+			.cloned()
+	}
+}
+
+impl FromStr for Version {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let components = s
+			.split('.')
+			.map(str::parse)
+			.collect::<Result<Vec<u64>, _>>()
+			.map_err(|_| anyhow!("Invalid version \"{s}\""))?;
+		Ok(Self(components))
+	}
+}
+
+impl Ord for Version {
+	fn cmp(&self, other: &Self) -> Ordering {
+		let len = self.0.len().max(other.0.len());
+		(0..len)
+			.map(|i| {
+				let a = self.0.get(i).copied().unwrap_or(0);
+				let b = other.0.get(i).copied().unwrap_or(0);
+				a.cmp(&b)
+			})
+			.find(|ordering| ordering.is_ne())
+			.unwrap_or(Ordering::Equal)
+	}
+}
+
+impl PartialOrd for Version {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Version {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other).is_eq()
+	}
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (i, component) in self.0.iter().enumerate() {
+			if i != 0 {
+				write!(f, ".")?;
+			}
+			write!(f, "{component}")?;
+		}
+		Ok(())
+	}
+}
+
+/// A program a module depends on. It is either given as a string like
+/// `"swaybg | hyprpaper"` or `"kitty >= 0.30"`, or as a table with the fields
+/// `program`, `min_version` and `version_args`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "DependencyDef")]
+pub struct Dependency {
+	/// Programs of which at least one has to be installed.
+	pub alternatives: Vec<String>,
+	pub min_version: Option<Version>,
+
+	/// The arguments that make the program print its version.
+	pub version_args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependencyDef {
+	Short(String),
+	Full(DependencyTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DependencyTable {
+	program: String,
+	#[serde(default)]
+	min_version: Option<Version>,
+	#[serde(default)]
+	version_args: Option<Vec<String>>,
+}
+
+impl TryFrom<DependencyDef> for Dependency {
+	type Error = anyhow::Error;
+
+	fn try_from(value: DependencyDef) -> Result<Self, Self::Error> {
+		match value {
+			DependencyDef::Short(dependency) => dependency.parse(),
+			DependencyDef::Full(table) => {
+				let mut dependency: Self = table.program.parse()?;
+				if table.min_version.is_some() {
+					dependency.min_version = table.min_version;
+				}
+				if let Some(version_args) = table.version_args {
+					dependency.version_args = version_args;
+				}
+				Ok(dependency)
+			}
+		}
+	}
+}
+
+impl FromStr for Dependency {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (programs, min_version) = match s.split_once(">=") {
+			Some((programs, version)) => (programs, Some(version.trim().parse()?)),
+			None => (s, None),
+		};
+
+		let alternatives: Vec<String> = programs
+			.split('|')
+			.map(str::trim)
+			.filter(|program| !program.is_empty())
+			.map(str::to_string)
+			.collect();
+		if alternatives.is_empty() {
+			return Err(anyhow!("Invalid dependency \"{s}\""));
+		}
+
+		Ok(Self {
+			alternatives,
+			min_version,
+			version_args: vec!["--version".to_string()],
+		})
+	}
+}
+
+impl fmt::Display for Dependency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.alternatives.join(" | "))?;
+		if let Some(min_version) = &self.min_version {
+			write!(f, " >= {min_version}")?;
+		}
+		Ok(())
+	}
+}
+
+impl Dependency {
+	/// Checks whether one of the alternatives is installed in a recent enough
+	/// version, and returns the reason if not.
+	pub fn check(&self) -> Result<(), String> {
+		let mut outdated = Vec::new();
+
+		for program in &self.alternatives {
+			debug!("Checking for module dependency {program}...");
+			let Some(path) = find_program(program) else {
+				continue;
+			};
+			debug!("Found {program} at {}", path.display());
+
+			let Some(min_version) = &self.min_version else {
+				return Ok(());
+			};
+			match self.probe_version(program) {
+				Some(version) if version >= *min_version => return Ok(()),
+				Some(version) => outdated.push(format!("{program} {version}")),
+				None => outdated.push(format!("{program} of unknown version")),
+			}
+		}
+
+		if outdated.is_empty() {
+			Err("not installed".to_string())
+		} else {
+			Err(format!("found {}", outdated.join(", ")))
+		}
+	}
+
+	fn probe_version(&self, program: &str) -> Option<Version> {
+		let output = Command::new(program)
+			.args(&self.version_args)
+			.output()
+			.inspect_err(|err| debug!("Failed to run {program}: {err}"))
+			.ok()?;
+
+		// Some programs print their version to stderr
+		let version = Version::find_in(&String::from_utf8_lossy(&output.stdout))
+			.or_else(|| Version::find_in(&String::from_utf8_lossy(&output.stderr)));
+		debug!(
+			"Version of {program} is {}",
+			version
+				.as_ref()
+				.map_or_else(|| "unknown".to_string(), Version::to_string)
+		);
+		version
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+	use tempfile::tempdir;
+
+	use super::*;
+
+	fn write_program(path: &Path, output: &str) {
+		fs::write(path, format!("#!/bin/sh\necho '{output}'\n")).unwrap();
+		fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+	}
+
+	#[test]
+	fn parse_dependency() {
+		let dependency: Dependency = "swaybg | hyprpaper".parse().unwrap();
+		assert_eq!(dependency.alternatives, vec!["swaybg", "hyprpaper"]);
+		assert_eq!(dependency.min_version, None);
+
+		let dependency: Dependency = "kitty >= 0.30".parse().unwrap();
+		assert_eq!(dependency.alternatives, vec!["kitty"]);
+		assert_eq!(dependency.min_version, Some("0.30".parse().unwrap()));
+		assert_eq!(dependency.to_string(), "kitty >= 0.30");
+
+		"kitty >= latest".parse::<Dependency>().unwrap_err();
+		" | ".parse::<Dependency>().unwrap_err();
+	}
+
+	#[test]
+	fn deserialize_dependency() {
+		#[derive(Deserialize)]
+		struct Manifest {
+			dependencies: Vec<Dependency>,
+		}
+
+		let manifest: Manifest = toml::from_str(
+			r#"dependencies = ["waybar", { program = "wob", min_version = "0.15", version_args = ["-V"] }]"#,
+		)
+		.unwrap();
+
+		assert_eq!(manifest.dependencies[0].alternatives, vec!["waybar"]);
+		assert_eq!(manifest.dependencies[1].alternatives, vec!["wob"]);
+		assert_eq!(manifest.dependencies[1].version_args, vec!["-V"]);
+		assert_eq!(
+			manifest.dependencies[1].min_version,
+			Some("0.15".parse().unwrap())
+		);
+	}
+
+	#[test]
+	fn compare_versions() {
+		let version = |s: &str| s.parse::<Version>().unwrap();
+
+		assert_eq!(version("1.2"), version("1.2.0"));
+		assert!(version("0.30.1") > version("0.30"));
+		assert!(version("0.9.22") < version("0.10"));
+		assert!(version("2") > version("1.99.99"));
+	}
+
+	#[test]
+	fn find_version_in_output() {
+		let find = |s: &str| Version::find_in(s).map(|v| v.to_string());
+
+		assert_eq!(
+			find("kitty 0.30.1 created by Kovid Goyal"),
+			Some("0.30.1".to_string())
+		);
+		assert_eq!(find("Waybar v0.9.22"), Some("0.9.22".to_string()));
+		assert_eq!(find("qt5ct 1.8"), Some("1.8".to_string()));
+		assert_eq!(find("foo version 3"), Some("3".to_string()));
+		assert_eq!(find("no version here"), None);
+	}
+
+	#[test]
+	fn check_dependency() {
+		let tempdir = tempdir().unwrap();
+		let program = tempdir.path().join("niji-test-program");
+		write_program(&program, "niji-test-program v1.4.2");
+		let program = program.to_string_lossy();
+
+		let check = |dependency: &str| dependency.parse::<Dependency>().unwrap().check();
+
+		check("sh").unwrap();
+		check("niji-nonexistent-program | sh").unwrap();
+		assert_eq!(
+			check("niji-nonexistent-program").unwrap_err(),
+			"not installed"
+		);
+		check(&format!("{program} >= 1.4")).unwrap();
+		assert_eq!(
+			check(&format!("{program} >= 1.10")).unwrap_err(),
+			format!("found {program} 1.4.2")
+		);
+	}
+}
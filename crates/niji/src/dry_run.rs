@@ -0,0 +1,217 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use similar::TextDiff;
+
+use crate::managed_fs;
+
+#[derive(Debug)]
+struct FileWrite {
+	path: PathBuf,
+	content: String,
+
+	/// Whether the file would be written using `managed_fs`, which asks before
+	/// overwriting files that were changed outside of niji.
+	managed: bool,
+}
+
+/// The changes a module would make when applied. While a lua state holds a
+/// `DryRun` as app data, the niji API records file writes, removals, renames
+/// and commands in it instead of performing them.
+#[derive(Debug, Default)]
+pub struct DryRun {
+	writes: Vec<FileWrite>,
+	removals: Vec<PathBuf>,
+	renames: Vec<(PathBuf, PathBuf)>,
+	commands: Vec<String>,
+}
+
+impl DryRun {
+	pub fn write(&mut self, path: PathBuf, content: String, managed: bool) {
+		// Only the last write to a file matters
+		self.take_write(&path);
+		self.removals.retain(|removed| *removed != path);
+		self.writes.push(FileWrite {
+			path,
+			content,
+			managed,
+		});
+	}
+
+	pub fn remove(&mut self, path: PathBuf) {
+		// Files that only exist because of an earlier write are just not
+		// written
+		if (self.take_write(&path).is_none() || path.exists()) && !self.removals.contains(&path) {
+			self.removals.push(path);
+		}
+	}
+
+	pub fn rename(&mut self, from: PathBuf, to: PathBuf) {
+		self.take_write(&to);
+		if let Some(mut write) = self.take_write(&from) {
+			if from.exists() {
+				self.removals.push(from);
+			}
+			write.path = to;
+			self.writes.push(write);
+		} else {
+			self.renames.push((from, to));
+		}
+	}
+
+	fn take_write(&mut self, path: &Path) -> Option<FileWrite> {
+		let index = self.writes.iter().position(|write| write.path == path)?;
+		Some(self.writes.remove(index))
+	}
+
+	pub fn run(&mut self, command: String) {
+		self.commands.push(command);
+	}
+
+	/// Returns the paths and contents of the files that would be written.
+	pub fn writes(&self) -> Vec<(&Path, &str)> {
+		self.writes
+			.iter()
+			.map(|write| (write.path.as_path(), write.content.as_str()))
+			.collect()
+	}
+
+	pub fn removals(&self) -> &[PathBuf] {
+		&self.removals
+	}
+
+	pub fn renames(&self) -> &[(PathBuf, PathBuf)] {
+		&self.renames
+	}
+
+	pub fn commands(&self) -> &[String] {
+		&self.commands
+	}
+
+	/// Prints the recorded writes as unified diffs against the current file
+	/// contents, followed by the recorded removals, renames and commands.
+	pub fn print(&self) {
+		if self.writes.is_empty()
+			&& self.removals.is_empty()
+			&& self.renames.is_empty()
+			&& self.commands.is_empty()
+		{
+			niji_console::println!("No files would be changed and no commands would be run");
+			return;
+		}
+
+		for write in &self.writes {
+			Self::print_write(write);
+		}
+
+		if !self.removals.is_empty() {
+			niji_console::println!("Files that would be removed:");
+			for path in &self.removals {
+				niji_console::println!("  {}", path.display());
+			}
+		}
+
+		if !self.renames.is_empty() {
+			niji_console::println!("Files that would be renamed:");
+			for (from, to) in &self.renames {
+				niji_console::println!("  {} -> {}", from.display(), to.display());
+			}
+		}
+
+		if !self.commands.is_empty() {
+			niji_console::println!("Commands that would be run:");
+			for command in &self.commands {
+				niji_console::println!("  {command}");
+			}
+		}
+	}
+
+	fn print_write(write: &FileWrite) {
+		let current = fs::read_to_string(&write.path).ok();
+		if current.as_deref() == Some(write.content.as_str()) {
+			niji_console::println!("{} would not change", write.path.display());
+			return;
+		}
+		if write.managed && managed_fs::would_prompt(&write.path) {
+			niji_console::println!(
+				"{} was changed outside of niji; you would be asked before it is overwritten",
+				write.path.display()
+			);
+		}
+
+		niji_console::println!("{}", diff(&write.path, current.as_deref(), &write.content));
+	}
+}
+
+fn diff(path: &Path, current: Option<&str>, new: &str) -> String {
+	let path = path.to_string_lossy();
+	let old_header = if current.is_some() {
+		path.as_ref()
+	} else {
+		"/dev/null"
+	};
+
+	TextDiff::from_lines(current.unwrap_or_default(), new)
+		.unified_diff()
+		.header(old_header, &path)
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn diff_files() {
+		let path = Path::new("/tmp/test.conf");
+
+		assert_eq!(
+			diff(path, Some("a = 1\nb = 2\n"), "a = 1\nb = 3\n"),
+			"--- /tmp/test.conf\n+++ /tmp/test.conf\n@@ -1,2 +1,2 @@\n a = 1\n-b = 2\n+b = 3\n"
+		);
+		assert_eq!(
+			diff(path, None, "a = 1\n"),
+			"--- /dev/null\n+++ /tmp/test.conf\n@@ -0,0 +1 @@\n+a = 1\n"
+		);
+	}
+
+	#[test]
+	fn keep_last_write() {
+		let mut dry_run = DryRun::default();
+		dry_run.write(PathBuf::from("/tmp/a"), "1".to_string(), true);
+		dry_run.write(PathBuf::from("/tmp/b"), "2".to_string(), false);
+		dry_run.write(PathBuf::from("/tmp/a"), "3".to_string(), true);
+
+		assert_eq!(
+			dry_run.writes(),
+			vec![(Path::new("/tmp/b"), "2"), (Path::new("/tmp/a"), "3")]
+		);
+	}
+
+	#[test]
+	fn remove_and_rename() {
+		let tempdir = tempfile::tempdir().unwrap();
+		let [a, b, c, new, renamed] =
+			["a", "b", "c", "new", "renamed"].map(|name| tempdir.path().join(name));
+		fs::write(&a, "").unwrap();
+		fs::write(&c, "").unwrap();
+
+		let mut dry_run = DryRun::default();
+		dry_run.write(new.clone(), "1".to_string(), false);
+		dry_run.rename(new, renamed.clone());
+		dry_run.rename(a.clone(), b.clone());
+		dry_run.remove(c.clone());
+		assert_eq!(dry_run.writes(), vec![(renamed.as_path(), "1")]);
+		assert_eq!(dry_run.renames(), [(a, b)]);
+		assert_eq!(dry_run.removals(), std::slice::from_ref(&c));
+
+		// Removing a file that would only be created just drops the write,
+		// and writing a removed file replaces the removal
+		dry_run.remove(renamed);
+		dry_run.write(c.clone(), "2".to_string(), false);
+		assert_eq!(dry_run.writes(), vec![(c.as_path(), "2")]);
+		assert!(dry_run.removals().is_empty());
+	}
+}
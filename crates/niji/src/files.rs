@@ -14,6 +14,9 @@ use crate::utils::{
 pub struct Files {
 	config_file: PathBuf,
 	state_file: PathBuf,
+	history_file: PathBuf,
+	lock_file: PathBuf,
+	fingerprints_file: PathBuf,
 	output_dir: PathBuf,
 	themes_dirs: Vec<PathBuf>,
 	modules_dirs: Vec<PathBuf>,
@@ -29,6 +32,9 @@ impl Files {
 	const PREFIX: &'static str = "niji";
 	const CONFIG_FILE: &'static str = "config.toml";
 	const STATE_FILE: &'static str = "state.toml";
+	const HISTORY_FILE: &'static str = "history.toml";
+	const LOCK_FILE: &'static str = "niji.lock";
+	const FINGERPRINTS_FILE: &'static str = "fingerprints.toml";
 	const THEMES_DIR: &'static str = "themes";
 	const THEME_MAIN_FILE_NAME: &'static str = "theme.toml";
 	const MODULES_DIR: &'static str = "modules";
@@ -44,6 +50,9 @@ impl Files {
 
 		let config_file = config_dir.join(Self::CONFIG_FILE);
 		let state_file = state_dir.join(Self::STATE_FILE);
+		let history_file = state_dir.join(Self::HISTORY_FILE);
+		let lock_file = state_dir.join(Self::LOCK_FILE);
+		let fingerprints_file = state_dir.join(Self::FINGERPRINTS_FILE);
 		let custom_themes_dir = config_dir.join(Self::THEMES_DIR);
 		let custom_modules_dir = config_dir.join(Self::MODULES_DIR);
 
@@ -89,6 +98,9 @@ impl Files {
 			config_file,
 			output_dir: data_dir,
 			state_file,
+			history_file,
+			lock_file,
+			fingerprints_file,
 			themes_dirs,
 			modules_dirs,
 		})
@@ -104,6 +116,27 @@ impl Files {
 		&self.state_file
 	}
 
+	#[inline]
+	pub fn history_file(&self) -> &Path {
+		&self.history_file
+	}
+
+	#[inline]
+	pub fn lock_file(&self) -> &Path {
+		&self.lock_file
+	}
+
+	#[inline]
+	pub fn fingerprints_file(&self) -> &Path {
+		&self.fingerprints_file
+	}
+
+	/// The directory that contains the user's own modules.
+	#[inline]
+	pub fn custom_modules_dir(&self) -> &Path {
+		&self.modules_dirs[0]
+	}
+
 	#[inline]
 	pub fn output_dir(&self) -> &Path {
 		&self.output_dir
@@ -174,6 +207,18 @@ mod tests {
 			files.state_file(),
 			xdg_dirs.state_home.join("niji/state.toml")
 		);
+		assert_eq!(
+			files.history_file(),
+			xdg_dirs.state_home.join("niji/history.toml")
+		);
+		assert_eq!(
+			files.lock_file(),
+			xdg_dirs.state_home.join("niji/niji.lock")
+		);
+		assert_eq!(
+			files.fingerprints_file(),
+			xdg_dirs.state_home.join("niji/fingerprints.toml")
+		);
 		assert_eq!(files.output_dir(), xdg_dirs.data_home.join("niji"));
 	}
 
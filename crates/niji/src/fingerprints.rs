@@ -0,0 +1,151 @@
+use std::{
+	collections::{HashMap, hash_map::DefaultHasher},
+	fs,
+	hash::{Hash, Hasher},
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+	config::{ModuleConfig, hash_map_sorted},
+	theme::Theme,
+	types::color::Color,
+	utils::fs::write_atomic,
+};
+
+/// The fingerprints of the inputs each module was last successfully applied
+/// with, used to skip modules whose inputs haven't changed.
+#[derive(Debug)]
+pub struct Fingerprints {
+	path: PathBuf,
+	entries: HashMap<String, String>,
+}
+
+impl Fingerprints {
+	pub fn load(path: &Path) -> Self {
+		let mut entries = HashMap::new();
+		if path.exists() {
+			match fs::read_to_string(path)
+				.context("Failed to read fingerprints file")
+				.and_then(|str| toml::from_str(&str).context("Invalid fingerprints file"))
+			{
+				Ok(e) => entries = e,
+				Err(err) => log::warn!("{err:#}\nAll modules will be re-applied."),
+			}
+		}
+
+		Self {
+			path: path.to_path_buf(),
+			entries,
+		}
+	}
+
+	pub fn is_current(&self, module: &str, fingerprint: &str) -> bool {
+		self.entries.get(module).is_some_and(|f| f == fingerprint)
+	}
+
+	pub fn insert(&mut self, module: String, fingerprint: String) {
+		self.entries.insert(module, fingerprint);
+	}
+
+	pub fn remove(&mut self, module: &str) {
+		self.entries.remove(module);
+	}
+
+	pub fn write(&self) -> anyhow::Result<()> {
+		let fingerprints_str = toml::to_string(&self.entries)?;
+		write_atomic(&self.path, fingerprints_str).context("Failed to write fingerprints file")?;
+		Ok(())
+	}
+}
+
+pub fn module_fingerprint(
+	module_dir: &Path,
+	config: &ModuleConfig,
+	theme: &Theme,
+	accent: Color,
+) -> anyhow::Result<String> {
+	let mut hasher = DefaultHasher::new();
+	env!("CARGO_PKG_VERSION").hash(&mut hasher);
+	theme.hash(&mut hasher);
+	accent.hash(&mut hasher);
+	hash_map_sorted(config, &mut hasher);
+	hash_dir(module_dir, module_dir, &mut hasher)
+		.with_context(|| format!("Failed to read module files in {}", module_dir.display()))?;
+	Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn hash_dir(root: &Path, dir: &Path, hasher: &mut impl Hasher) -> anyhow::Result<()> {
+	let mut paths = fs::read_dir(dir)?
+		.map(|entry| entry.map(|e| e.path()))
+		.collect::<Result<Vec<_>, _>>()?;
+	paths.sort_unstable();
+
+	for path in paths {
+		path.strip_prefix(root)?.hash(hasher);
+		if path.is_dir() {
+			hash_dir(root, &path, hasher)?;
+		} else {
+			fs::read(&path)?.hash(hasher);
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use crate::{config::ModuleConfigValue, theme::test_utils::test_theme};
+
+	use super::*;
+
+	#[test]
+	fn fingerprint_changes_with_inputs() {
+		let tempdir = tempdir().unwrap();
+		fs::write(tempdir.path().join("module.lua"), "return {}").unwrap();
+
+		let theme = test_theme();
+		let mut config = ModuleConfig::new();
+		let fingerprint =
+			module_fingerprint(tempdir.path(), &config, &theme, Color::BLACK).unwrap();
+		assert_eq!(
+			module_fingerprint(tempdir.path(), &config, &theme, Color::BLACK).unwrap(),
+			fingerprint
+		);
+
+		assert_ne!(
+			module_fingerprint(tempdir.path(), &config, &theme, Color::WHITE).unwrap(),
+			fingerprint
+		);
+
+		config.insert("font_scale".to_string(), ModuleConfigValue::Float(1.5));
+		assert_ne!(
+			module_fingerprint(tempdir.path(), &config, &theme, Color::BLACK).unwrap(),
+			fingerprint
+		);
+		config.clear();
+
+		fs::write(tempdir.path().join("template.mustache"), "{{background}}").unwrap();
+		assert_ne!(
+			module_fingerprint(tempdir.path(), &config, &theme, Color::BLACK).unwrap(),
+			fingerprint
+		);
+	}
+
+	#[test]
+	fn persist_fingerprints() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("fingerprints.toml");
+
+		let mut fingerprints = Fingerprints::load(&path);
+		assert!(!fingerprints.is_current("test", "abc"));
+		fingerprints.insert("test".to_string(), "abc".to_string());
+		fingerprints.write().unwrap();
+
+		let fingerprints = Fingerprints::load(&path);
+		assert!(fingerprints.is_current("test", "abc"));
+		assert!(!fingerprints.is_current("test", "def"));
+	}
+}
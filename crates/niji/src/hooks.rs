@@ -0,0 +1,384 @@
+use std::{
+	fmt, io,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::{Context, anyhow};
+use niji_macros::IntoLua;
+use serde::{Deserialize, Serialize};
+use serde_with::{OneOrMany, formats::PreferOne, serde_as};
+
+use crate::{
+	lua::runtime::{LuaRuntime, LuaRuntimeInit},
+	theme::{ColorRef, Theme},
+	types::color::Color,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+	PreApply,
+	PostApply,
+	ThemeChange,
+	AccentChange,
+}
+
+impl fmt::Display for HookEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::PreApply => write!(f, "pre_apply"),
+			Self::PostApply => write!(f, "post_apply"),
+			Self::ThemeChange => write!(f, "on_theme_change"),
+			Self::AccentChange => write!(f, "on_accent_change"),
+		}
+	}
+}
+
+/// What happens if a hook fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureMode {
+	/// Skip the remaining hooks and exit with an error. A failing `pre_apply`
+	/// hook also prevents the modules from being applied.
+	Abort,
+
+	#[default]
+	Warn,
+
+	Ignore,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookAction {
+	/// A command that is run using `sh -c`.
+	Command(String),
+
+	/// A lua file that is run with the niji API available.
+	Lua(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Hook {
+	Command(String),
+	Detailed {
+		#[serde(flatten)]
+		action: HookAction,
+
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		on_failure: Option<FailureMode>,
+	},
+}
+
+/// The `[hooks]` section of the config.
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+	/// The failure mode of hooks that don't specify their own.
+	pub on_failure: FailureMode,
+
+	#[serde_as(as = "OneOrMany<_, PreferOne>")]
+	pub pre_apply: Vec<Hook>,
+
+	#[serde_as(as = "OneOrMany<_, PreferOne>")]
+	pub post_apply: Vec<Hook>,
+
+	#[serde_as(as = "OneOrMany<_, PreferOne>")]
+	pub on_theme_change: Vec<Hook>,
+
+	#[serde_as(as = "OneOrMany<_, PreferOne>")]
+	pub on_accent_change: Vec<Hook>,
+}
+
+/// The state that is passed to hooks.
+#[derive(Debug, Clone, Default)]
+pub struct HookContext {
+	pub theme: Option<Theme>,
+	pub accent: Option<String>,
+
+	/// The modules that are about to be applied for `pre_apply`, and the
+	/// modules that were applied otherwise.
+	pub modules: Vec<String>,
+
+	/// The previous theme for `on_theme_change`, and the previous accent color
+	/// for `on_accent_change`.
+	pub previous: Option<String>,
+}
+
+impl HookContext {
+	fn accent_color(&self) -> Option<Color> {
+		let (theme, accent) = self.theme.as_ref().zip(self.accent.as_ref())?;
+		ColorRef::named(accent).resolve(&theme.palette).ok()
+	}
+
+	fn env(&self, event: HookEvent) -> Vec<(&'static str, String)> {
+		let mut env = vec![
+			("NIJI_HOOK", event.to_string()),
+			("NIJI_MODULES", self.modules.join(" ")),
+		];
+		if let Some(theme) = &self.theme {
+			env.push(("NIJI_THEME", theme.name.clone()));
+			env.push(("NIJI_THEME_KIND", theme.kind.to_string()));
+		}
+		if let Some(accent) = &self.accent {
+			env.push(("NIJI_ACCENT", accent.clone()));
+		}
+		if let Some(color) = self.accent_color() {
+			env.push(("NIJI_ACCENT_COLOR", color.to_string()));
+		}
+		match (event, &self.previous) {
+			(HookEvent::ThemeChange, Some(previous)) => {
+				env.push(("NIJI_PREVIOUS_THEME", previous.clone()));
+			}
+			(HookEvent::AccentChange, Some(previous)) => {
+				env.push(("NIJI_PREVIOUS_ACCENT", previous.clone()));
+			}
+			_ => (),
+		}
+		env
+	}
+}
+
+/// The table that is passed to lua hooks.
+#[derive(IntoLua)]
+struct LuaHookContext {
+	event: String,
+	theme: Option<Theme>,
+	accent_name: Option<String>,
+	accent: Option<Color>,
+	modules: Vec<String>,
+	previous: Option<String>,
+}
+
+impl Hooks {
+	fn get(&self, event: HookEvent) -> &[Hook] {
+		match event {
+			HookEvent::PreApply => &self.pre_apply,
+			HookEvent::PostApply => &self.post_apply,
+			HookEvent::ThemeChange => &self.on_theme_change,
+			HookEvent::AccentChange => &self.on_accent_change,
+		}
+	}
+
+	/// Runs the hooks for `event` in order, handling failures according to
+	/// their failure mode.
+	pub fn run(
+		&self,
+		event: HookEvent,
+		context: &HookContext,
+		runtime_init: &LuaRuntimeInit,
+	) -> anyhow::Result<()> {
+		for hook in self.get(event) {
+			log::debug!("Running {event} hook {hook}");
+			let Err(err) = hook.run(event, context, runtime_init) else {
+				continue;
+			};
+			match hook.on_failure().unwrap_or(self.on_failure) {
+				FailureMode::Abort => {
+					return Err(err.context(format!("The {event} hook {hook} failed")));
+				}
+				FailureMode::Warn => log::warn!("The {event} hook {hook} failed: {err:#}"),
+				FailureMode::Ignore => log::debug!("The {event} hook {hook} failed: {err:#}"),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Hook {
+	fn action(&self) -> HookAction {
+		match self {
+			Self::Command(command) => HookAction::Command(command.clone()),
+			Self::Detailed { action, .. } => action.clone(),
+		}
+	}
+
+	fn on_failure(&self) -> Option<FailureMode> {
+		match self {
+			Self::Command(_) => None,
+			Self::Detailed { on_failure, .. } => *on_failure,
+		}
+	}
+
+	fn run(
+		&self,
+		event: HookEvent,
+		context: &HookContext,
+		runtime_init: &LuaRuntimeInit,
+	) -> anyhow::Result<()> {
+		// Relative paths are relative to the niji config directory
+		let config_dir = runtime_init
+			.files
+			.config_file()
+			.parent()
+			.unwrap_or(Path::new("/"));
+
+		match self.action() {
+			HookAction::Command(command) => {
+				// The output goes to stderr, so that it doesn't end up in the
+				// JSON output
+				let status = Command::new("sh")
+					.args(["-c", &command])
+					.current_dir(config_dir)
+					.envs(context.env(event))
+					.stdout(io::stderr())
+					.status()
+					.context("Failed to run sh")?;
+				if !status.success() {
+					return Err(anyhow!("`{command}` exited with {status}"));
+				}
+				Ok(())
+			}
+			HookAction::Lua(path) => {
+				let path = config_dir.join(shellexpand::tilde(&path.to_string_lossy()).as_ref());
+				let lua_context = LuaHookContext {
+					event: event.to_string(),
+					theme: context.theme.clone(),
+					accent_name: context.accent.clone(),
+					accent: context.accent_color(),
+					modules: context.modules.clone(),
+					previous: context.previous.clone(),
+				};
+				LuaRuntime::new(runtime_init.clone())?.run_script(&path, lua_context)
+			}
+		}
+	}
+}
+
+impl fmt::Display for Hook {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.action() {
+			HookAction::Command(command) => write!(f, "`{command}`"),
+			HookAction::Lua(path) => write!(f, "{}", path.display()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, sync::Arc};
+
+	use tempfile::tempdir;
+
+	use super::*;
+	use crate::{files::Files, theme::test_utils::test_theme, utils::xdg::XdgDirs};
+
+	#[test]
+	fn parse_hooks() {
+		let hooks: Hooks = toml::from_str(
+			r#"
+				on_failure = "abort"
+				pre_apply = "echo pre"
+				post_apply = ["echo post", { lua = "hooks/post.lua", on_failure = "ignore" }]
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(hooks.on_failure, FailureMode::Abort);
+		assert_eq!(hooks.pre_apply, vec![Hook::Command("echo pre".to_string())]);
+		assert_eq!(
+			hooks.post_apply[1],
+			Hook::Detailed {
+				action: HookAction::Lua(PathBuf::from("hooks/post.lua")),
+				on_failure: Some(FailureMode::Ignore),
+			}
+		);
+		assert!(hooks.on_theme_change.is_empty());
+	}
+
+	#[test]
+	fn run_hooks() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime_init = LuaRuntimeInit { xdg, files };
+		let config_dir = runtime_init.files.config_file().parent().unwrap();
+		fs::write(
+			config_dir.join("hook.lua"),
+			r#"
+				local context = ...
+				local file = io.open(context.modules[1], "w")
+				file:write(context.event .. " " .. context.theme.name .. " " .. context.accent_name)
+				file:close()
+			"#,
+		)
+		.unwrap();
+
+		let hooks: Hooks = toml::from_str(
+			r#"
+				on_theme_change = [
+					"echo $NIJI_HOOK $NIJI_THEME $NIJI_THEME_KIND $NIJI_PREVIOUS_THEME > shell.out",
+					{ lua = "hook.lua" },
+				]
+				pre_apply = ["false", "echo $NIJI_ACCENT $NIJI_ACCENT_COLOR > pre.out"]
+				post_apply = [{ command = "false", on_failure = "abort" }, "touch post.out"]
+			"#,
+		)
+		.unwrap();
+		let context = HookContext {
+			theme: Some(test_theme()),
+			accent: Some("red".to_string()),
+			modules: vec![config_dir.join("lua.out").to_string_lossy().into_owned()],
+			previous: Some("old".to_string()),
+		};
+
+		hooks
+			.run(HookEvent::ThemeChange, &context, &runtime_init)
+			.unwrap();
+		assert_eq!(
+			fs::read_to_string(config_dir.join("shell.out")).unwrap(),
+			format!("on_theme_change {} dark old\n", test_theme().name)
+		);
+		assert_eq!(
+			fs::read_to_string(config_dir.join("lua.out")).unwrap(),
+			format!("on_theme_change {} red", test_theme().name)
+		);
+
+		// Failures only cause a warning by default
+		hooks
+			.run(HookEvent::PreApply, &context, &runtime_init)
+			.unwrap();
+		assert!(config_dir.join("pre.out").exists());
+
+		hooks
+			.run(HookEvent::PostApply, &context, &runtime_init)
+			.unwrap_err();
+		assert!(!config_dir.join("post.out").exists());
+	}
+
+	#[test]
+	fn module_api_in_lua_hook() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime_init = LuaRuntimeInit { xdg, files };
+		let config_dir = runtime_init.files.config_file().parent().unwrap();
+		let context = HookContext {
+			theme: None,
+			accent: None,
+			modules: vec![],
+			previous: None,
+		};
+
+		// Hooks aren't modules, so the module-scoped API fails instead of
+		// panicking
+		for source in [
+			"print(niji.mod.name)",
+			"niji.fs.output_unchecked('out.txt', '')",
+		] {
+			fs::write(config_dir.join("hook.lua"), source).unwrap();
+			let err = Hook::Detailed {
+				action: HookAction::Lua(PathBuf::from("hook.lua")),
+				on_failure: None,
+			}
+			.run(HookEvent::PostApply, &context, &runtime_init)
+			.unwrap_err();
+			assert!(
+				format!("{err:#}").contains("only available to modules"),
+				"{err:#}"
+			);
+		}
+	}
+}
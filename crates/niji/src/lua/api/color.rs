@@ -31,6 +31,12 @@ impl UserData for Color {
 		methods.add_method("with_alpha", |_, this, alpha: f32| {
 			Ok(this.with_alpha(alpha))
 		});
+		methods.add_method("contrast_ratio", |_, this, other: Color| {
+			Ok(this.contrast_ratio(other))
+		});
+		methods.add_method("apca", |_, this, background: Color| {
+			Ok(this.apca(background))
+		});
 
 		methods.add_meta_method("__tostring", |_, this, ()| Ok(this.to_string()));
 	}
@@ -61,7 +67,7 @@ impl ApiModule for Color {
 
 #[cfg(test)]
 mod tests {
-	use std::{fs, rc::Rc};
+	use std::{fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -74,8 +80,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -89,6 +95,8 @@ mod tests {
                 assert(niji.Color.darken, "niji.Color.darken not defined!")
                 assert(niji.Color.shade, "niji.Color.darken not defined!")
                 assert(niji.Color.with_alpha, "niji.Color.with_alpha not defined!")
+                assert(niji.Color.contrast_ratio, "niji.Color.contrast_ratio not defined!")
+                assert(niji.Color.apca, "niji.Color.apca not defined!")
                 assert(niji.Color.r, "niji.Color.r not defined!")
                 assert(niji.Color.g, "niji.Color.g not defined!")
                 assert(niji.Color.b, "niji.Color.b not defined!")
@@ -50,7 +50,7 @@ impl ApiModule for ConsoleApi {
 
 #[cfg(test)]
 mod tests {
-	use std::{fs, rc::Rc};
+	use std::{fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -63,8 +63,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
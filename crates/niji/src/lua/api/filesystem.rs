@@ -1,11 +1,17 @@
-use std::{fs, path::PathBuf, rc::Rc};
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
 
 use log::info;
 use mlua::{FromLua, IntoLua, Lua, chunk};
 
-use crate::{files::Files, lua::api::ModuleContext, managed_fs, utils::xdg::XdgDirs};
+use crate::{
+	dry_run::DryRun, files::Files, managed_fs, transaction::Transaction, utils::xdg::XdgDirs,
+};
 
-use super::ApiModule;
+use super::{ApiModule, module_context, resolve_path};
 
 pub struct FilesystemApi;
 
@@ -35,13 +41,33 @@ fn get_value_or_list<V: FromLua>(lua: &Lua, value: mlua::Value) -> mlua::Result<
 	}
 }
 
+/// Creates the parent directory of `path`, and takes a snapshot of the file if
+/// the module is applied in a transaction.
+fn prepare_write(lua: &Lua, path: &Path) -> mlua::Result<()> {
+	let dir = path.parent().unwrap();
+	match lua.app_data_mut::<Transaction>() {
+		Some(mut transaction) => {
+			transaction
+				.create_dir_all(dir)
+				.map_err(mlua::Error::runtime)?;
+			transaction.snapshot(path).map_err(mlua::Error::runtime)
+		}
+		None => fs::create_dir_all(dir).map_err(mlua::Error::runtime),
+	}
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[allow(clippy::unnecessary_wraps)]
 impl FilesystemApi {
-	fn write(_: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
-		let path = expand_path(&path);
+	fn write(lua: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
+		let path = resolve_path(lua, &path);
 
-		fs::create_dir_all(path.parent().unwrap()).map_err(mlua::Error::runtime)?;
+		if let Some(mut dry_run) = lua.app_data_mut::<DryRun>() {
+			dry_run.write(path.clone(), content, true);
+			return Ok(path.to_string_lossy().into_owned());
+		}
+
+		prepare_write(lua, &path)?;
 
 		log::info!("Writing to {}", path.display());
 		managed_fs::write(&path, &content).map_err(mlua::Error::runtime)?;
@@ -50,7 +76,7 @@ impl FilesystemApi {
 	}
 
 	fn write_config(lua: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		Self::write(
 			lua,
 			(
@@ -61,7 +87,7 @@ impl FilesystemApi {
 	}
 
 	fn write_state(lua: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		Self::write(
 			lua,
 			(
@@ -72,7 +98,7 @@ impl FilesystemApi {
 	}
 
 	fn write_data(lua: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		Self::write(
 			lua,
 			(
@@ -83,34 +109,39 @@ impl FilesystemApi {
 	}
 
 	fn read_config(lua: &'_ Lua, path: String) -> mlua::Result<String> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		fs::read_to_string(xdg.config_home.join(expand_path(&path))).map_err(mlua::Error::runtime)
 	}
 
 	fn read_state(lua: &'_ Lua, path: String) -> mlua::Result<mlua::Value> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		fs::read_to_string(xdg.state_home.join(expand_path(&path)))
 			.map_err(mlua::Error::runtime)?
 			.into_lua(lua)
 	}
 
 	fn read_data(lua: &'_ Lua, path: String) -> mlua::Result<mlua::Value> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		fs::read_to_string(xdg.data_home.join(path))
 			.map_err(mlua::Error::runtime)?
 			.into_lua(lua)
 	}
 
 	fn output_unchecked(lua: &Lua, (path, content): (String, String)) -> mlua::Result<String> {
-		let mod_ctx = lua.app_data_ref::<ModuleContext>().unwrap();
-		let files = lua.app_data_ref::<Rc<Files>>().unwrap();
+		let mod_ctx = module_context(lua)?;
+		let files = lua.app_data_ref::<Arc<Files>>().unwrap();
 		let path = files
 			.output_dir()
 			.join(&mod_ctx.name)
 			.join(expand_path(&path));
 
+		if let Some(mut dry_run) = lua.app_data_mut::<DryRun>() {
+			dry_run.write(path.clone(), content, false);
+			return Ok(path.to_string_lossy().into_owned());
+		}
+
 		info!("Outputting to {}", path.display());
-		fs::create_dir_all(path.parent().unwrap()).map_err(mlua::Error::runtime)?;
+		prepare_write(lua, &path)?;
 		fs::write(&path, content).map_err(mlua::Error::runtime)?;
 		Ok(path.to_string_lossy().into_owned())
 	}
@@ -119,8 +150,8 @@ impl FilesystemApi {
 		lua: &Lua,
 		(config, options): (mlua::Table, mlua::Table),
 	) -> mlua::Result<String> {
-		let mod_ctx = lua.app_data_ref::<ModuleContext>().unwrap();
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let mod_ctx = module_context(lua)?;
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		let path = Self::output_unchecked(lua, (options.get("out")?, options.get("content")?))?;
 
 		if config
@@ -143,10 +174,10 @@ impl FilesystemApi {
 		} else {
 			let paths = options.get::<mlua::Value>("sourced_by_path")?;
 			for path in get_value_or_list::<String>(lua, paths)? {
-				if fs::exists(&path).map_err(mlua::Error::runtime)? {
-					check_files.push(
-						fs::read_to_string(expand_path(&path)).map_err(mlua::Error::runtime)?,
-					);
+				let resolved_path = resolve_path(lua, &path);
+				if fs::exists(&resolved_path).map_err(mlua::Error::runtime)? {
+					check_files
+						.push(fs::read_to_string(&resolved_path).map_err(mlua::Error::runtime)?);
 				}
 				config_paths.push(path);
 			}
@@ -204,14 +235,14 @@ impl FilesystemApi {
 	}
 
 	fn get_output_dir(lua: &Lua, (): ()) -> mlua::Result<String> {
-		let mod_ctx = lua.app_data_ref::<ModuleContext>().unwrap();
-		let files = lua.app_data_ref::<Rc<Files>>().unwrap();
+		let mod_ctx = module_context(lua)?;
+		let files = lua.app_data_ref::<Arc<Files>>().unwrap();
 		let path = files.output_dir().join(&mod_ctx.name);
 		Ok(path.to_string_lossy().into_owned())
 	}
 
 	fn read_config_asset(lua: &'_ Lua, path: String) -> mlua::Result<mlua::Value> {
-		let files = lua.app_data_ref::<Rc<Files>>().unwrap();
+		let files = lua.app_data_ref::<Arc<Files>>().unwrap();
 		let path = files
 			.config_file()
 			.parent()
@@ -271,8 +302,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -1,6 +1,6 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{path::PathBuf, sync::Arc};
 
-use mlua::Lua;
+use mlua::{AppDataRef, Lua};
 
 use crate::{files::Files, types::color::Color, utils::xdg::XdgDirs};
 
@@ -36,9 +36,13 @@ fn insert_module<M: ApiModule>(lua: &Lua, api: &mlua::Table) -> mlua::Result<()>
 
 const API_GLOBAL: &str = "niji";
 
+/// The version of the lua API. This has to be increased whenever a feature is
+/// added to the API, so that modules can declare which version they require.
+pub const API_VERSION: u32 = 1;
+
 pub struct Init {
-	pub xdg: Rc<XdgDirs>,
-	pub files: Rc<Files>,
+	pub xdg: Arc<XdgDirs>,
+	pub files: Arc<Files>,
 }
 
 pub fn init(lua: &Lua, init: Init) -> mlua::Result<()> {
@@ -56,6 +60,7 @@ pub fn init(lua: &Lua, init: Init) -> mlua::Result<()> {
 	insert_module::<LuaTemplate>(lua, &api)?;
 	insert_module::<OsApi>(lua, &api)?;
 	insert_module::<UtilApi>(lua, &api)?;
+	api.raw_set("api_version", API_VERSION)?;
 
 	lua.globals().set(API_GLOBAL, api)?;
 
@@ -69,3 +74,19 @@ pub fn set_module_context(lua: &Lua, ctx: ModuleContext) {
 pub fn reset_module_context(lua: &Lua) {
 	lua.remove_app_data::<ModuleContext>();
 }
+
+/// Returns the context of the module that is calling the API. Hooks and other
+/// scripts aren't modules, so the module-scoped functions fail for them.
+fn module_context(lua: &Lua) -> mlua::Result<AppDataRef<'_, ModuleContext>> {
+	lua.app_data_ref::<ModuleContext>()
+		.ok_or_else(|| mlua::Error::runtime("This is only available to modules"))
+}
+
+/// Resolves a path passed by a module relative to the module directory.
+fn resolve_path(lua: &Lua, path: &str) -> PathBuf {
+	let path = PathBuf::from(&*shellexpand::tilde(path));
+	match lua.app_data_ref::<ModuleContext>() {
+		Some(ctx) => ctx.path.join(path),
+		None => path,
+	}
+}
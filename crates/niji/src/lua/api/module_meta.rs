@@ -1,6 +1,6 @@
 use mlua::{IntoLua, Lua};
 
-use super::{ApiModule, ModuleContext};
+use super::{ApiModule, module_context};
 
 pub struct ModuleMetaApi;
 
@@ -12,7 +12,7 @@ impl ApiModule for ModuleMetaApi {
 		meta.raw_set(
 			"__index",
 			lua.create_function(|lua, (_, index): (mlua::Value, String)| {
-				let module_ctx = lua.app_data_ref::<ModuleContext>().unwrap();
+				let module_ctx = module_context(lua)?;
 				match index.as_str() {
 					"name" => Ok(module_ctx.name.clone().into_lua(lua)?),
 					"path" => Ok(module_ctx.path.to_string_lossy().into_lua(lua)?),
@@ -29,7 +29,7 @@ impl ApiModule for ModuleMetaApi {
 
 #[cfg(test)]
 mod tests {
-	use std::{fs, rc::Rc};
+	use std::{fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -42,8 +42,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -64,8 +64,8 @@ mod tests {
 	#[test]
 	fn is_correct() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -2,17 +2,26 @@ use std::process::Command;
 
 use mlua::{IntoLua, Lua};
 
-use super::ApiModule;
+use crate::dry_run::DryRun;
+
+use super::{ApiModule, ModuleContext};
 
 pub struct OsApi;
 
 #[allow(clippy::needless_pass_by_value)]
 impl OsApi {
-	fn exec_detached(_: &Lua, command: String) -> mlua::Result<()> {
-		Command::new("sh")
-			.args(["-c", &command])
-			.spawn()
-			.map_err(mlua::Error::runtime)?;
+	fn exec_detached(lua: &Lua, command: String) -> mlua::Result<()> {
+		if let Some(mut dry_run) = lua.app_data_mut::<DryRun>() {
+			dry_run.run(command);
+			return Ok(());
+		}
+
+		let mut cmd = Command::new("sh");
+		cmd.args(["-c", &command]);
+		if let Some(ctx) = lua.app_data_ref::<ModuleContext>() {
+			cmd.current_dir(&ctx.path);
+		}
+		cmd.spawn().map_err(mlua::Error::runtime)?;
 
 		Ok(())
 	}
@@ -32,7 +41,7 @@ impl ApiModule for OsApi {
 
 #[cfg(test)]
 mod tests {
-	use std::{fs, rc::Rc};
+	use std::{fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -45,8 +54,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -4,7 +4,7 @@ use crate::{template::load_template, types::color::Color};
 use mlua::{IntoLua, UserData, UserDataMethods};
 use niji_templates::Template;
 
-use super::ApiModule;
+use super::{ApiModule, resolve_path};
 
 fn is_array(table: &mlua::Table) -> bool {
 	for i in 1..=table.clone().pairs::<mlua::Value, mlua::Value>().count() {
@@ -91,7 +91,7 @@ impl From<Template> for LuaTemplate {
 impl UserData for LuaTemplate {
 	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
 		methods.add_method("load", |lua, _, path: String| {
-			let template = load_template(&path).map_err(|e| {
+			let template = load_template(resolve_path(lua, &path)).map_err(|e| {
 				mlua::Error::runtime(format!("Failed to load template {path}: {e}"))
 			})?;
 			LuaTemplate(template).into_lua(lua)
@@ -132,7 +132,7 @@ impl ApiModule for LuaTemplate {
 
 #[cfg(test)]
 mod tests {
-	use std::{fs, rc::Rc};
+	use std::{fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -145,8 +145,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
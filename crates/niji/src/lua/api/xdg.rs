@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use mlua::IntoLua;
 
@@ -12,7 +12,7 @@ impl ApiModule for XdgApi {
 	const NAMESPACE: &'static str = "xdg";
 
 	fn build(lua: &'_ mlua::Lua) -> mlua::Result<mlua::Value> {
-		let xdg = lua.app_data_ref::<Rc<XdgDirs>>().unwrap();
+		let xdg = lua.app_data_ref::<Arc<XdgDirs>>().unwrap();
 		xdg.as_ref().clone().into_lua(lua)
 	}
 }
@@ -33,8 +33,8 @@ mod tests {
 	#[test]
 	fn is_accessible() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit { xdg, files }).unwrap();
 
 		fs::write(
@@ -0,0 +1,208 @@
+local resolve, record = ...
+
+local open, lines, input, output = io.open, io.lines, io.input, io.output
+local close, write, io_type, tmpfile = io.close, io.write, io.type, io.tmpfile
+
+-- The contents of the files written during the dry run, or false for the
+-- files that were removed, so that the module reads back its own changes
+local pending = {}
+
+-- Maps the handles returned for files opened for writing to the temporary
+-- files that hold their contents
+local handles = setmetatable({}, { __mode = "k" })
+
+-- The handle io.output is set to, if it belongs to a file opened for writing
+local current_output
+
+local function not_found(path)
+	return nil, path .. ": No such file or directory", 2
+end
+
+local function read(path)
+	if pending[path] ~= nil then
+		return pending[path] or nil
+	end
+	local file = open(path, "rb")
+	if not file then
+		return nil
+	end
+	local content = file:read("*a")
+	file:close()
+	return content or ""
+end
+
+local function buffer(content)
+	local file = tmpfile()
+	if content then
+		file:write(content)
+		file:seek("set")
+	end
+	return file
+end
+
+-- Returns a handle that behaves like a file opened for writing, but records
+-- its contents instead of writing them to `path`
+local function writer(path, content)
+	local file = buffer(content)
+	local handle = {}
+
+	local function save()
+		local position = file:seek()
+		file:seek("set")
+		pending[path] = file:read("*a")
+		file:seek("set", position)
+		record.write(path, pending[path])
+	end
+
+	setmetatable(handle, {
+		__index = function(_, key)
+			local method = file[key]
+			if type(method) ~= "function" then
+				return method
+			end
+			return function(_, ...)
+				if key == "close" then
+					save()
+					if current_output == handle then
+						current_output = nil
+					end
+				end
+				local results = { method(file, ...) }
+				if results[1] == file then
+					results[1] = handle
+				end
+				if key ~= "close" and io_type(file) == "file" then
+					save()
+				end
+				return unpack(results)
+			end
+		end,
+		__tostring = function()
+			return tostring(file)
+		end,
+	})
+	handles[handle] = file
+	save()
+	return handle
+end
+
+function io.open(path, mode)
+	path = resolve(path)
+	mode = mode or "r"
+	if not mode:find("[wa+]") then
+		if pending[path] == false then
+			return not_found(path)
+		elseif pending[path] then
+			return buffer(pending[path])
+		end
+		return open(path, mode)
+	end
+
+	local content
+	if mode:sub(1, 1) ~= "w" then
+		content = read(path)
+		if not content and mode:sub(1, 1) == "r" then
+			return not_found(path)
+		end
+	end
+	local handle = writer(path, content)
+	if mode:sub(1, 1) == "a" then
+		handle:seek("end")
+	end
+	return handle
+end
+
+function io.lines(path, ...)
+	if path ~= nil then
+		path = resolve(path)
+		if pending[path] == false then
+			error(path .. ": No such file or directory", 2)
+		elseif pending[path] then
+			return buffer(pending[path]):lines(...)
+		end
+	end
+	return lines(path, ...)
+end
+
+function io.input(file)
+	if type(file) == "string" then
+		file = resolve(file)
+		if pending[file] == false then
+			error(file .. ": No such file or directory", 2)
+		elseif pending[file] then
+			file = buffer(pending[file])
+		end
+	end
+	return input(file)
+end
+
+function io.output(file)
+	if type(file) == "string" then
+		file = writer(resolve(file))
+	end
+	if file == nil then
+		return current_output or output()
+	end
+	current_output = handles[file] and file
+	output(handles[file] or file)
+	return file
+end
+
+function io.write(...)
+	if current_output then
+		return current_output:write(...)
+	end
+	return write(...)
+end
+
+function io.close(file)
+	file = file or current_output
+	if file == nil then
+		return close()
+	elseif handles[file] then
+		return file:close()
+	end
+	return close(file)
+end
+
+function io.type(file)
+	if handles[file] then
+		return io_type(handles[file])
+	end
+	return io_type(file)
+end
+
+function io.popen(command, mode)
+	record.run(command)
+	return open("/dev/null", mode)
+end
+
+function os.execute(command)
+	if command == nil then
+		return 1
+	end
+	record.run(command)
+	return 0
+end
+
+function os.remove(path)
+	path = resolve(path)
+	if not read(path) then
+		return not_found(path)
+	end
+	pending[path] = false
+	record.remove(path)
+	return true
+end
+
+function os.rename(old_path, new_path)
+	old_path, new_path = resolve(old_path), resolve(new_path)
+	local content = read(old_path)
+	if not content then
+		return not_found(old_path)
+	end
+	pending[old_path] = false
+	pending[new_path] = content
+	record.rename(old_path, new_path)
+	return true
+end
@@ -0,0 +1,63 @@
+local dir = ...
+
+local function resolve(path)
+	if type(path) ~= "string" or path:sub(1, 1) == "/" then
+		return path
+	end
+	return dir .. "/" .. path
+end
+
+local function in_dir(command)
+	if type(command) ~= "string" then
+		return command
+	end
+	return "cd '" .. dir:gsub("'", "'\\''") .. "' && " .. command
+end
+
+local open, lines, input, output, popen = io.open, io.lines, io.input, io.output, io.popen
+local execute, remove, rename = os.execute, os.remove, os.rename
+local do_file, load_file = dofile, loadfile
+
+function io.open(path, ...)
+	return open(resolve(path), ...)
+end
+
+function io.lines(path, ...)
+	return lines(resolve(path), ...)
+end
+
+function io.input(file)
+	return input(resolve(file))
+end
+
+function io.output(file)
+	return output(resolve(file))
+end
+
+function io.popen(command, ...)
+	return popen(in_dir(command), ...)
+end
+
+function os.execute(command)
+	return execute(in_dir(command))
+end
+
+function os.remove(path)
+	return remove(resolve(path))
+end
+
+function os.rename(old_path, new_path)
+	return rename(resolve(old_path), resolve(new_path))
+end
+
+function dofile(path)
+	return do_file(resolve(path))
+end
+
+function loadfile(path, ...)
+	return load_file(resolve(path), ...)
+end
+
+package.path = dir .. "/?.lua;" .. dir .. "/?/init.lua;" .. package.path
+
+return resolve
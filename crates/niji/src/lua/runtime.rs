@@ -1,120 +1,151 @@
-use std::{
-	env,
-	path::{Path, PathBuf},
-	rc::Rc,
-};
+use std::{path::Path, sync::Arc};
 
 use anyhow::anyhow;
 use log::debug;
-use mlua::{FromLuaMulti, IntoLuaMulti, Lua};
+use mlua::{FromLua, FromLuaMulti, IntoLuaMulti, Lua};
 
-use crate::{files::Files, utils::xdg::XdgDirs};
+use crate::{dry_run::DryRun, files::Files, transaction::Transaction, utils::xdg::XdgDirs};
 
 use super::api::{self, ModuleContext};
 
+#[derive(Clone)]
 pub struct LuaRuntimeInit {
-	pub xdg: Rc<XdgDirs>,
-	pub files: Rc<Files>,
+	pub xdg: Arc<XdgDirs>,
+	pub files: Arc<Files>,
 }
 
+/// A lua state that hosts a single module. Every module gets its own state, so
+/// that modules can be applied concurrently.
 pub struct LuaRuntime {
 	lua: Lua,
 }
 
 #[derive(Debug)]
-pub struct LuaModule<'a> {
-	lua: &'a Lua,
-	name: String,
-	directory: PathBuf,
-	table: Option<mlua::Table>,
+pub struct LuaModule {
+	lua: Lua,
+	table: mlua::Table,
 }
 
-impl<'a> LuaModule<'a> {
+impl LuaModule {
 	const ENTRY_POINT: &'static str = "module.lua";
 
-	fn new(lua: &'a Lua, directory: PathBuf) -> Self {
-		Self {
-			lua,
-			name: directory
-				.file_name()
-				.unwrap()
-				.to_string_lossy()
-				.into_owned(),
-			directory,
-			table: None,
+	/// Sets up the lua standard library to resolve relative paths relative to
+	/// the module directory, instead of the process-wide working directory.
+	const RELATIVE_PATHS: &'static str = include_str!("relative_paths.lua");
+
+	/// Replaces the functions of the lua standard library that run commands or
+	/// write, remove or rename files, so that the changes are only recorded.
+	const DRY_RUN: &'static str = include_str!("dry_run.lua");
+
+	fn load(lua: Lua, directory: &Path) -> anyhow::Result<Self> {
+		let name = directory
+			.file_name()
+			.unwrap()
+			.to_string_lossy()
+			.into_owned();
+
+		api::set_module_context(
+			&lua,
+			ModuleContext {
+				name: name.clone(),
+				path: directory.to_path_buf(),
+			},
+		);
+		// Printed lines go through the console, so that the output of modules
+		// that are applied concurrently doesn't get mixed up
+		replace_print(&lua, |line| niji_console::println!("{line}"))?;
+		let resolve: mlua::Function = lua
+			.load(Self::RELATIVE_PATHS)
+			.set_name("relative_paths.lua")
+			.call(directory.to_string_lossy())?;
+		if lua.app_data_ref::<DryRun>().is_some() {
+			lua.load(Self::DRY_RUN)
+				.set_name("dry_run.lua")
+				.call::<()>((resolve, Self::dry_run_recorder(&lua)?))?;
 		}
-	}
 
-	fn load(&mut self) -> anyhow::Result<()> {
-		let chunk = self.lua.load(self.directory.join(Self::ENTRY_POINT));
-		let value: mlua::Value = self.in_context(self.lua, || chunk.call(()))?;
+		let value: mlua::Value = lua.load(directory.join(Self::ENTRY_POINT)).call(())?;
 		let mlua::Value::Table(table) = value else {
 			return Err(anyhow!(
-				"Expected lua module `{}` to return a table, but received {} instead",
-				self.name,
+				"Expected lua module `{name}` to return a table, but received {} instead",
 				value.type_name()
 			));
 		};
 
-		self.table = Some(table);
-
-		debug!("Loaded lua module {}", self.directory.display());
-		Ok(())
+		debug!("Loaded lua module {}", directory.display());
+		Ok(Self { lua, table })
 	}
 
-	pub fn has_function(&'a self, key: &str) -> mlua::Result<bool> {
-		let table = self.get_table()?;
+	/// Creates the functions `dry_run.lua` uses to record the changes in the
+	/// `DryRun`.
+	fn dry_run_recorder(lua: &Lua) -> mlua::Result<mlua::Table> {
+		fn record<A: FromLuaMulti>(
+			lua: &Lua,
+			cb: impl Fn(&mut DryRun, A) + Send + 'static,
+		) -> mlua::Result<mlua::Function> {
+			lua.create_function(move |lua, args: A| {
+				if let Some(mut dry_run) = lua.app_data_mut::<DryRun>() {
+					cb(&mut dry_run, args);
+				}
+				Ok(())
+			})
+		}
+
+		let recorder = lua.create_table()?;
+		recorder.set(
+			"run",
+			record(lua, |dry_run, command: String| dry_run.run(command))?,
+		)?;
+		recorder.set(
+			"write",
+			record(lua, |dry_run, (path, content): (String, mlua::String)| {
+				dry_run.write(path.into(), content.to_string_lossy(), false);
+			})?,
+		)?;
+		recorder.set(
+			"remove",
+			record(lua, |dry_run, path: String| dry_run.remove(path.into()))?,
+		)?;
+		recorder.set(
+			"rename",
+			record(lua, |dry_run, (from, to): (String, String)| {
+				dry_run.rename(from.into(), to.into());
+			})?,
+		)?;
+		Ok(recorder)
+	}
 
-		let Some(value) = table.get::<Option<mlua::Value>>(key)? else {
+	pub fn has_function(&self, key: &str) -> mlua::Result<bool> {
+		let Some(value) = self.table.get::<Option<mlua::Value>>(key)? else {
 			return Ok(false);
 		};
 
 		Ok(matches!(value, mlua::Value::Function(..)))
 	}
 
+	pub fn get<V: FromLua>(&self, key: &str) -> mlua::Result<V> {
+		self.table.get(key)
+	}
+
 	pub fn call<A, R>(&self, key: &str, args: A) -> mlua::Result<R>
 	where
 		A: IntoLuaMulti,
 		R: FromLuaMulti,
 	{
-		let table = self.get_table()?;
-
-		let function: mlua::Function = table.get(key)?;
-		self.in_context(self.lua, move || function.call(args))
+		let function: mlua::Function = self.table.get(key)?;
+		function.call(args)
 	}
 
-	fn get_table(&self) -> mlua::Result<&mlua::Table> {
-		let Some(table) = &self.table else {
-			return Err(mlua::Error::runtime(format!(
-				"Module {} is not loaded yet!",
-				self.name
-			)));
-		};
-		Ok(table)
+	/// Returns the changes recorded since the module was loaded in dry-run
+	/// mode.
+	pub fn take_dry_run(&self) -> Option<DryRun> {
+		self.lua.remove_app_data()
 	}
 
-	fn in_context<R>(&self, lua: &'a Lua, cb: impl FnOnce() -> mlua::Result<R>) -> mlua::Result<R> {
-		let prev_dir = env::current_dir().unwrap_or_else(|_| {
-			log::error!("Current working directory is inaccessible! defaulting to home directory");
-			env::home_dir().unwrap()
-		});
-		env::set_current_dir(&self.directory).unwrap();
-		api::set_module_context(
-			lua,
-			ModuleContext {
-				name: self.name.clone(),
-				path: self.directory.clone(),
-			},
-		);
-
-		let result: R = cb()?;
-
-		api::reset_module_context(lua);
-		if let Err(err) = env::set_current_dir(prev_dir) {
-			log::error!("Cannot reset working directory: {err}\n defaulting to home directory");
-			env::set_current_dir(env::home_dir().unwrap()).unwrap();
-		}
-		Ok(result)
+	/// Returns the snapshots of the files written since the module was loaded
+	/// in a transaction.
+	pub fn take_transaction(&self) -> Option<Transaction> {
+		self.lua.remove_app_data()
 	}
 }
 
@@ -134,11 +165,44 @@ impl LuaRuntime {
 		Ok(Self { lua })
 	}
 
-	pub fn load_lua_module(&self, path: &Path) -> anyhow::Result<LuaModule<'_>> {
-		let mut module = LuaModule::new(&self.lua, path.to_path_buf());
-		module.load()?;
-		Ok(module)
+	/// Makes modules loaded by this runtime record the files they write and
+	/// the commands they run, instead of performing them.
+	pub fn enable_dry_run(&self) {
+		self.lua.set_app_data(DryRun::default());
 	}
+
+	/// Makes modules loaded by this runtime take a snapshot of every file
+	/// before writing it, so that the changes can be rolled back.
+	pub fn begin_transaction(&self) {
+		self.lua.set_app_data(Transaction::default());
+	}
+
+	pub fn load_lua_module(self, path: &Path) -> anyhow::Result<LuaModule> {
+		LuaModule::load(self.lua, path)
+	}
+
+	/// Runs a standalone lua file, like a hook, passing `args` to it. Like the
+	/// output of command hooks, `print` writes to stderr.
+	pub fn run_script(self, path: &Path, args: impl IntoLuaMulti) -> anyhow::Result<()> {
+		replace_print(&self.lua, |line| eprintln!("{line}"))?;
+		self.lua.load(path).call::<()>(args)?;
+		debug!("Ran lua script {}", path.display());
+		Ok(())
+	}
+}
+
+/// Replaces the global `print` function, so that the printed lines are passed
+/// to `print_line` instead of being written to stdout.
+fn replace_print(lua: &Lua, print_line: fn(&str)) -> mlua::Result<()> {
+	let print = lua.create_function(move |_, values: mlua::Variadic<mlua::Value>| {
+		let values = values
+			.iter()
+			.map(mlua::Value::to_string)
+			.collect::<mlua::Result<Vec<_>>>()?;
+		print_line(&values.join("\t"));
+		Ok(())
+	})?;
+	lua.globals().set("print", print)
 }
 
 #[cfg(test)]
@@ -152,8 +216,8 @@ mod tests {
 	#[test]
 	fn init() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -164,8 +228,8 @@ mod tests {
 	#[test]
 	fn load_module() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -187,8 +251,8 @@ mod tests {
 	#[test]
 	fn load_module_error_not_a_table() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
@@ -211,8 +275,8 @@ mod tests {
 	#[test]
 	fn load_module_syntax_error() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
@@ -235,8 +299,8 @@ mod tests {
 	#[test]
 	fn has_function() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -262,8 +326,8 @@ mod tests {
 	#[test]
 	fn call_function() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -286,4 +350,121 @@ mod tests {
 			"Argument was `:3`".to_string()
 		);
 	}
+
+	#[test]
+	fn resolve_relative_paths() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: xdg.clone(),
+			files,
+		})
+		.unwrap();
+
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(module_dir.join("util.lua"), "return { value = 'util' }").unwrap();
+		fs::write(module_dir.join("data.txt"), "data").unwrap();
+		fs::write(module_dir.join("old.txt"), "").unwrap();
+		fs::write(module_dir.join("cache.txt"), "").unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			r#"
+				local util = require("./util")
+				local file = io.open("data.txt")
+				local data = file:read("*a")
+				file:close()
+				assert(os.rename("old.txt", "new.txt"))
+				assert(os.remove("cache.txt"))
+				local pwd = io.popen("pwd"):read("*l")
+				return { result = util.value .. " " .. data .. " " .. pwd }
+			"#,
+		)
+		.unwrap();
+
+		let module = runtime.load_lua_module(&module_dir).unwrap();
+
+		assert_eq!(
+			module.get::<String>("result").unwrap(),
+			format!("util data {}", module_dir.display())
+		);
+		assert!(module_dir.join("new.txt").exists());
+		assert!(!module_dir.join("old.txt").exists());
+		assert!(!module_dir.join("cache.txt").exists());
+	}
+
+	#[test]
+	fn dry_run_std_lib() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: xdg.clone(),
+			files,
+		})
+		.unwrap();
+		runtime.enable_dry_run();
+
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(module_dir.join("log.txt"), "a\n").unwrap();
+		fs::write(module_dir.join("old.txt"), "old").unwrap();
+		fs::write(module_dir.join("cache.txt"), "").unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			r#"
+				local file = assert(io.open("out.txt", "w"))
+				file:write("hello"):write(" world")
+				file:close()
+				assert(io.open("out.txt"):read("*a") == "hello world")
+
+				assert(io.open("log.txt", "a")):write("b\n")
+				io.output("output.txt")
+				io.write("written with io.write")
+				io.close()
+
+				assert(os.rename("old.txt", "new.txt"))
+				assert(os.remove("cache.txt"))
+				assert(not os.remove("cache.txt"))
+				assert(not io.open("cache.txt"))
+				assert(os.execute("touch touched.txt") == 0)
+				return {}
+			"#,
+		)
+		.unwrap();
+
+		let module = runtime.load_lua_module(&module_dir).unwrap();
+		let dry_run = module.take_dry_run().unwrap();
+
+		assert_eq!(
+			dry_run.writes(),
+			vec![
+				(module_dir.join("out.txt").as_path(), "hello world"),
+				(module_dir.join("log.txt").as_path(), "a\nb\n"),
+				(
+					module_dir.join("output.txt").as_path(),
+					"written with io.write"
+				),
+			]
+		);
+		assert_eq!(
+			dry_run.renames(),
+			[(module_dir.join("old.txt"), module_dir.join("new.txt"))]
+		);
+		assert_eq!(dry_run.removals(), [module_dir.join("cache.txt")]);
+		assert_eq!(dry_run.commands(), ["touch touched.txt"]);
+
+		// Nothing was actually changed
+		let mut entries = fs::read_dir(&module_dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect::<Vec<_>>();
+		entries.sort();
+		assert_eq!(entries, ["cache.txt", "log.txt", "module.lua", "old.txt"]);
+		assert_eq!(
+			fs::read_to_string(module_dir.join("log.txt")).unwrap(),
+			"a\n"
+		);
+	}
 }
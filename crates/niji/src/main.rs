@@ -1,17 +1,28 @@
 use std::process::ExitCode;
 
 mod app;
+mod apply_report;
 mod cli;
 mod config;
+mod config_editor;
+mod dependency;
+mod dry_run;
 mod files;
+mod fingerprints;
+mod hooks;
 mod lua;
 mod managed_fs;
 mod module;
 mod module_manager;
+mod module_manifest;
+mod module_test;
+mod scaffold;
+mod schema;
 mod state_manager;
 mod template;
 mod theme;
 mod theme_manager;
+mod transaction;
 mod types;
 mod utils;
 
@@ -20,6 +20,21 @@ pub fn write(path: &Path, string: &str) -> anyhow::Result<()> {
 	}
 }
 
+/// Returns whether writing to `path` would ask the user for confirmation,
+/// because the file was changed outside of niji.
+pub fn would_prompt(path: &Path) -> bool {
+	if !path.exists() {
+		return false;
+	}
+	let Ok(current_hash) = hash_contents(path) else {
+		return true;
+	};
+	get_managed_hash(path)
+		.ok()
+		.flatten()
+		.is_none_or(|known_hash| known_hash != current_hash)
+}
+
 fn init_new_file(path: &Path, string: &str) -> anyhow::Result<()> {
 	fs::write(path, string).context(format!("Failed to write to {}", path.display()))?;
 	set_managed(path)?;
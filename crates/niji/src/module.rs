@@ -1,81 +1,100 @@
-use std::{
-	fs::File,
-	io::{BufRead, BufReader},
-	path::Path,
-	process::{Command, Stdio},
-};
+use std::path::Path;
 
 use anyhow::anyhow;
-use log::debug;
+use log::warn;
 
 use crate::{
-	config::ModuleConfig,
+	config::{ModuleConfig, ModuleConfigValue},
+	dry_run::DryRun,
 	lua::runtime::{LuaModule, LuaRuntime},
+	module_manifest::ModuleManifest,
+	schema::ConfigSchema,
 	theme::Theme,
+	transaction::Transaction,
 	types::color::Color,
 };
 
 #[derive(Debug)]
-pub struct Module<'lua>(LuaModule<'lua>);
-
-impl<'lua> Module<'lua> {
-	const DEPS_FILE: &'static str = "deps.txt";
+pub struct Module {
+	lua: LuaModule,
+	manifest: ModuleManifest,
+}
 
-	pub fn load(runtime: &'lua LuaRuntime, path: &Path, check_deps: bool) -> anyhow::Result<Self> {
+impl Module {
+	pub fn load(runtime: LuaRuntime, path: &Path, check_deps: bool) -> anyhow::Result<Self> {
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		let manifest = ModuleManifest::load(path)?;
+		manifest.check_compatibility(&name)?;
 		if check_deps {
-			Self::check_dependencies(path)?;
+			Self::check_dependencies(&name, &manifest)?;
 		}
-		let module = runtime.load_lua_module(path)?;
-		Ok(Self(module))
+		let lua = runtime.load_lua_module(path)?;
+		Ok(Self { lua, manifest })
+	}
+
+	pub fn manifest(&self) -> &ModuleManifest {
+		&self.manifest
 	}
 
 	pub fn can_reload(&self) -> bool {
-		self.0.has_function("reload").unwrap_or(false)
+		self.lua.has_function("reload").unwrap_or(false)
+	}
+
+	/// The schema declared in the manifest takes precedence over the one
+	/// declared in lua.
+	pub fn schema(&self) -> anyhow::Result<Option<ConfigSchema>> {
+		if let Some(schema) = &self.manifest.schema {
+			return Ok(Some(schema.clone()));
+		}
+		match self.lua.get::<ModuleConfigValue>("schema")? {
+			ModuleConfigValue::Nil => Ok(None),
+			value => Ok(Some(ConfigSchema::from_value(&value)?)),
+		}
 	}
 
 	pub fn apply(&self, config: ModuleConfig, theme: Theme, accent: Color) -> anyhow::Result<()> {
-		if !self.0.has_function("apply")? {
+		if !self.lua.has_function("apply")? {
 			return Err(anyhow!("Module is missing an apply function"));
 		}
 
-		Ok(self.0.call("apply", (config, theme, accent))?)
+		Ok(self.lua.call("apply", (config, theme, accent))?)
 	}
 
-	pub fn reload(&self, config: ModuleConfig) -> anyhow::Result<()> {
-		Ok(self.0.call("reload", config)?)
+	pub fn take_dry_run(&self) -> Option<DryRun> {
+		self.lua.take_dry_run()
 	}
 
-	fn check_dependencies(path: &Path) -> anyhow::Result<()> {
-		let deps_file = path.join(Self::DEPS_FILE);
-		if !deps_file.exists() {
-			return Ok(());
-		}
-
-		let reader = BufReader::new(File::open(deps_file)?).lines();
-		for dependency in reader {
-			Self::check_dependency(&dependency?)?;
-		}
-
-		Ok(())
+	pub fn take_transaction(&self) -> Option<Transaction> {
+		self.lua.take_transaction()
 	}
 
-	fn check_dependency(program: &str) -> anyhow::Result<()> {
-		debug!("Checking for module dependency {program}...");
-
-		let output = Command::new("which")
-			.arg(program)
-			.stdout(Stdio::piped())
-			.output()
-			.expect("Failed to run which");
+	pub fn reload(&self, config: ModuleConfig) -> anyhow::Result<()> {
+		Ok(self.lua.call("reload", config)?)
+	}
 
-		if !output.status.success() {
-			return Err(anyhow!("Missing dependency: {program}"));
+	/// Checks all dependencies at once, so that every missing dependency is
+	/// reported. Missing optional dependencies only cause a warning.
+	fn check_dependencies(name: &str, manifest: &ModuleManifest) -> anyhow::Result<()> {
+		for dependency in &manifest.optional_dependencies {
+			if let Err(reason) = dependency.check() {
+				warn!("Module {name} is missing optional dependency {dependency} ({reason})");
+			}
 		}
 
-		debug!(
-			"Found {program} at {}",
-			String::from_utf8_lossy(&output.stdout).trim()
-		);
+		let missing: Vec<String> = manifest
+			.dependencies
+			.iter()
+			.filter_map(|dependency| {
+				let reason = dependency.check().err()?;
+				Some(format!("{dependency} ({reason})"))
+			})
+			.collect();
+		if !missing.is_empty() {
+			return Err(anyhow!(
+				"Module {name} is missing dependencies: {}",
+				missing.join(", ")
+			));
+		}
 
 		Ok(())
 	}
@@ -83,7 +102,7 @@ impl<'lua> Module<'lua> {
 
 #[cfg(test)]
 mod tests {
-	use std::{collections::HashMap, fs, rc::Rc};
+	use std::{collections::HashMap, fs, sync::Arc};
 
 	use tempfile::tempdir;
 
@@ -97,8 +116,8 @@ mod tests {
 	#[test]
 	fn load() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -112,14 +131,14 @@ mod tests {
 		)
 		.unwrap();
 
-		Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+		Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 	}
 
 	#[test]
 	fn load_syntax_error() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -133,14 +152,14 @@ mod tests {
 		)
 		.unwrap();
 
-		Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap_err();
+		Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap_err();
 	}
 
 	#[test]
 	fn load_not_a_module_error() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -154,14 +173,49 @@ mod tests {
 		)
 		.unwrap();
 
-		Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap_err();
+		Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap_err();
+	}
+
+	#[test]
+	fn report_all_missing_dependencies() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: xdg.clone(),
+			files,
+		})
+		.unwrap();
+
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			"return { apply = function(config, theme) end }",
+		)
+		.unwrap();
+		fs::write(
+			module_dir.join("module.toml"),
+			r#"
+				dependencies = ["sh", "niji-missing-1", "niji-missing-2 | niji-missing-3"]
+				optional_dependencies = ["niji-missing-4"]
+			"#,
+		)
+		.unwrap();
+
+		let err = Module::load(runtime, &module_dir, true).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"Module test is missing dependencies: niji-missing-1 (not installed), niji-missing-2 \
+			 | niji-missing-3 (not installed)"
+		);
 	}
 
 	#[test]
 	fn check_can_reload_false() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -176,15 +230,15 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		assert!(!module.can_reload());
 	}
 
 	#[test]
 	fn check_can_reload_true() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -199,15 +253,50 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		assert!(module.can_reload());
 	}
 
+	#[test]
+	fn schema() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: xdg.clone(),
+			files,
+		})
+		.unwrap();
+
+		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/test/module.lua"),
+			"return { schema = { padding = { type = 'int', default = 4 }, position = { type = \
+			 'string', values = { 'top', 'bottom' } } } }",
+		)
+		.unwrap();
+
+		let module =
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+		let schema = module.schema().unwrap().unwrap();
+		assert!(matches!(
+			schema.0["padding"].default,
+			Some(ModuleConfigValue::Int(4))
+		));
+		assert_eq!(
+			schema.0["position"].values,
+			Some(vec![
+				ModuleConfigValue::String("top".to_string()),
+				ModuleConfigValue::String("bottom".to_string())
+			])
+		);
+	}
+
 	#[test]
 	fn apply() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -222,7 +311,7 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		module
 			.apply(HashMap::new(), test_theme(), Color::BLACK)
 			.unwrap();
@@ -231,8 +320,8 @@ mod tests {
 	#[test]
 	fn apply_error() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -247,7 +336,7 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		module
 			.apply(HashMap::new(), test_theme(), Color::BLACK)
 			.unwrap_err();
@@ -256,8 +345,8 @@ mod tests {
 	#[test]
 	fn reload() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -272,15 +361,15 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		module.reload(ModuleConfig::new()).unwrap();
 	}
 
 	#[test]
 	fn reload_error() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		let runtime = LuaRuntime::new(LuaRuntimeInit {
 			xdg: xdg.clone(),
 			files,
@@ -295,7 +384,7 @@ mod tests {
 		.unwrap();
 
 		let module =
-			Module::load(&runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
+			Module::load(runtime, &xdg.config_home.join("niji/modules/test"), true).unwrap();
 		module.reload(ModuleConfig::new()).unwrap_err();
 	}
 }
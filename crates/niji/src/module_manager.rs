@@ -1,28 +1,59 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{
+	collections::{BTreeSet, HashMap, HashSet},
+	panic::{self, AssertUnwindSafe},
+	path::PathBuf,
+	sync::{Arc, Condvar, Mutex, mpsc},
+	thread,
+	time::{Duration, Instant},
+};
 
 use anyhow::{Context, anyhow};
-use log::{debug, error, info};
-use niji_console::heading;
+use log::{debug, error, info, warn};
 
 use crate::{
+	apply_report::{ApplyReport, ModuleReport, ModuleStatus},
 	config::Config,
 	files::Files,
+	fingerprints::{Fingerprints, module_fingerprint},
 	lua::runtime::{LuaRuntime, LuaRuntimeInit},
 	module::Module,
+	module_manifest::{ModuleManifest, ReloadBehavior},
 	theme::Theme,
 	types::color::Color,
 	utils::xdg::XdgDirs,
 };
 
 pub struct ModuleManagerInit {
-	pub xdg: Rc<XdgDirs>,
-	pub files: Rc<Files>,
+	pub xdg: Arc<XdgDirs>,
+	pub files: Arc<Files>,
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ApplyParams {
 	pub reload: bool,
 	pub check_deps: bool,
+	pub force: bool,
+
+	/// Print the changes the modules would make instead of making them.
+	pub dry_run: bool,
+
+	/// Apply the modules one after the other, and stop at the first failure.
+	pub fail_fast: bool,
+
+	/// Keep the files a module wrote if applying it fails, instead of
+	/// restoring their previous state.
+	pub keep_partial: bool,
+}
+
+/// A module found in one of the module directories. Modules in directories
+/// with a higher priority shadow modules of the same name in other
+/// directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleListing {
+	pub name: String,
+	pub path: PathBuf,
+	pub shadowed: bool,
 }
 
 #[derive(Clone)]
@@ -32,24 +63,43 @@ struct ModuleDescriptor {
 }
 
 pub struct ModuleManager {
-	files: Rc<Files>,
-	lua_runtime: LuaRuntime,
+	xdg: Arc<XdgDirs>,
+	files: Arc<Files>,
 }
 
 impl ModuleManager {
-	pub fn new(ModuleManagerInit { xdg, files }: ModuleManagerInit) -> anyhow::Result<Self> {
-		let lua_runtime = LuaRuntime::new(LuaRuntimeInit {
-			xdg: Rc::clone(&xdg),
-			files: Rc::clone(&files),
-		})
-		.context("Failed to initialize lua runtime")?;
+	pub fn new(ModuleManagerInit { xdg, files }: ModuleManagerInit) -> Self {
+		Self { xdg, files }
+	}
 
-		Ok(Self {
-			files: Rc::clone(&files),
-			lua_runtime,
-		})
+	/// Lists all modules in the module directories, sorted by name. Modules of
+	/// the same name are ordered by priority.
+	pub fn list(&self) -> Vec<ModuleListing> {
+		let mut found = HashSet::new();
+		let mut listings: Vec<ModuleListing> = self
+			.files
+			.iter_modules()
+			.map(|location| ModuleListing {
+				shadowed: !found.insert(location.name.clone()),
+				name: location.name,
+				path: location.path,
+			})
+			.collect();
+		listings.sort_by(|a, b| a.name.cmp(&b.name));
+		listings
 	}
 
+	pub fn find(&self, name: &str) -> anyhow::Result<PathBuf> {
+		Self::find_module_dir(&self.files, name)
+			.ok_or_else(|| anyhow!("Module \"{name}\" does not exist"))
+	}
+
+	/// Applies the given modules. Modules that don't depend on each other are
+	/// applied concurrently, each in its own lua state. Their output is
+	/// buffered, and printed in the order in which they are applied.
+	///
+	/// Errors of individual modules don't cause this to fail; they are
+	/// recorded in the returned report instead.
 	pub fn apply(
 		&self,
 		config: &Config,
@@ -57,13 +107,255 @@ impl ModuleManager {
 		accent: Color,
 		params: &ApplyParams,
 		modules: &[String],
-	) -> anyhow::Result<()> {
+	) -> anyhow::Result<ApplyReport> {
+		let mut loaded = Vec::with_capacity(modules.len());
 		for mod_name in modules {
 			let module_descr = Self::load(&self.files, mod_name)?;
-			self.apply_module(&module_descr, config, theme, accent, params);
+			let module = self.load_module(&module_descr, params);
+			loaded.push((module_descr, module));
 		}
 
-		Ok(())
+		let mut prerequisites = Self::resolve_prerequisites(config, &loaded);
+		let order = Self::sort_topologically(&loaded, &prerequisites)?;
+		if params.fail_fast {
+			// Apply the modules one after the other, so that no module is started
+			// after one has failed
+			for pair in order.windows(2) {
+				prerequisites[pair[1]] = BTreeSet::from([pair[0]]);
+			}
+		}
+
+		let fingerprints = Mutex::new(Fingerprints::load(self.files.fingerprints_file()));
+		let results: Mutex<Vec<Option<bool>>> = Mutex::new(vec![None; loaded.len()]);
+		let results_changed = Condvar::new();
+		let (output_tx, output_rx) = mpsc::channel();
+		let mut reports = Vec::with_capacity(loaded.len());
+
+		thread::scope(|scope| {
+			for (i, (module_descr, module)) in loaded.iter().enumerate() {
+				let prerequisites = &prerequisites[i];
+				let (fingerprints, results, results_changed) =
+					(&fingerprints, &results, &results_changed);
+				let output_tx = output_tx.clone();
+				let loaded = &loaded;
+
+				scope.spawn(move || {
+					let failed_prerequisite = {
+						let mut results = results.lock().unwrap();
+						while prerequisites.iter().any(|p| results[*p].is_none()) {
+							results = results_changed.wait(results).unwrap();
+						}
+						prerequisites
+							.iter()
+							.find(|p| results[**p] == Some(false))
+							.copied()
+					};
+
+					let start = Instant::now();
+					let (report, output) = niji_console::api::capture(&module_descr.name, || {
+						if let Some(prerequisite) = failed_prerequisite {
+							let reason = if params.fail_fast {
+								"a previous module failed to apply".to_string()
+							} else {
+								format!("{} failed to apply", loaded[prerequisite].0.name)
+							};
+							error!("Skipping module {}, since {reason}", module_descr.name);
+							niji_console::println!();
+							return ModuleReport {
+								name: module_descr.name.clone(),
+								status: ModuleStatus::Skipped,
+								duration: Duration::ZERO,
+								messages: vec![reason],
+							};
+						}
+						Self::apply_and_report(
+							module_descr,
+							module.as_ref(),
+							config,
+							theme,
+							accent,
+							params,
+							fingerprints,
+						)
+					});
+					let report = ModuleReport {
+						duration: start.elapsed(),
+						..report
+					};
+
+					let success = !matches!(
+						(report.status, failed_prerequisite),
+						(ModuleStatus::Failed, _) | (_, Some(_))
+					);
+					results.lock().unwrap()[i] = Some(success);
+					results_changed.notify_all();
+					output_tx.send((i, output, report)).unwrap();
+				});
+			}
+			drop(output_tx);
+
+			let mut outputs = HashMap::new();
+			for i in order {
+				while !outputs.contains_key(&i) {
+					let Ok((j, output, report)) = output_rx.recv() else {
+						return;
+					};
+					outputs.insert(j, (output, report));
+				}
+				let (output, report) = outputs.remove(&i).unwrap();
+				niji_console::api::print_captured(&output).unwrap();
+				reports.push(report);
+			}
+		});
+
+		if !params.dry_run {
+			fingerprints.into_inner().unwrap().write()?;
+		}
+		Ok(ApplyReport { modules: reports })
+	}
+
+	/// Applies a module, logging and recording any error.
+	#[allow(clippy::too_many_arguments)]
+	fn apply_and_report(
+		module_descr: &ModuleDescriptor,
+		module: Result<&Module, &anyhow::Error>,
+		config: &Config,
+		theme: &Theme,
+		accent: Color,
+		params: &ApplyParams,
+		fingerprints: &Mutex<Fingerprints>,
+	) -> ModuleReport {
+		let module = match module {
+			Ok(module) => module,
+			Err(err) => {
+				error!("{err:#}");
+				niji_console::println!();
+				return ModuleReport::failed(&module_descr.name, Duration::ZERO, err);
+			}
+		};
+
+		let result = panic::catch_unwind(AssertUnwindSafe(|| {
+			Self::apply_module(
+				module_descr,
+				module,
+				config,
+				theme,
+				accent,
+				params,
+				fingerprints,
+			)
+		}))
+		.unwrap_or_else(|_| Err(anyhow!("Module {} panicked", module_descr.name)));
+
+		match result {
+			Ok(status) => ModuleReport {
+				name: module_descr.name.clone(),
+				status,
+				duration: Duration::ZERO,
+				messages: if status == ModuleStatus::Skipped {
+					vec!["unchanged since the last application".to_string()]
+				} else {
+					vec![]
+				},
+			},
+			Err(err) => {
+				error!("{err:#}");
+				if let Some(dry_run) = module.take_dry_run() {
+					info!("Changes recorded before the module failed:");
+					dry_run.print();
+				}
+				error!("Aborting module execution");
+				Self::roll_back(module);
+				niji_console::println!();
+				ModuleReport::failed(&module_descr.name, Duration::ZERO, &err)
+			}
+		}
+	}
+
+	fn load_module(
+		&self,
+		module_descr: &ModuleDescriptor,
+		params: &ApplyParams,
+	) -> anyhow::Result<Module> {
+		let lua_runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: Arc::clone(&self.xdg),
+			files: Arc::clone(&self.files),
+		})
+		.context("Failed to initialize lua runtime")?;
+		if params.dry_run {
+			lua_runtime.enable_dry_run();
+		} else if !params.keep_partial {
+			lua_runtime.begin_transaction();
+		}
+
+		Module::load(lua_runtime, &module_descr.path, params.check_deps)
+	}
+
+	/// Finds the indices of the modules each module has to be applied after,
+	/// based on the ordering declared by the modules, and overridden by the
+	/// config.
+	fn resolve_prerequisites(
+		config: &Config,
+		loaded: &[(ModuleDescriptor, anyhow::Result<Module>)],
+	) -> Vec<BTreeSet<usize>> {
+		let index_of = |name: &str| loaded.iter().position(|(descr, _)| descr.name == name);
+
+		let mut prerequisites = vec![BTreeSet::new(); loaded.len()];
+		for (i, (module_descr, module)) in loaded.iter().enumerate() {
+			let mut order = match module {
+				Ok(module) => module.manifest().order(),
+				// The manifest may still be valid if the module failed to load
+				Err(_) => ModuleManifest::load(&module_descr.path)
+					.map(|manifest| manifest.order())
+					.unwrap_or_default(),
+			};
+			if let Some(order_override) = config.module_order.get(&module_descr.name) {
+				order.override_with(order_override.clone());
+			}
+
+			for after in order.after.unwrap_or_default() {
+				if let Some(j) = index_of(&after) {
+					prerequisites[i].insert(j);
+				}
+			}
+			for before in order.before.unwrap_or_default() {
+				if let Some(j) = index_of(&before) {
+					prerequisites[j].insert(i);
+				}
+			}
+		}
+		prerequisites
+	}
+
+	/// Orders the modules so that every module comes after its prerequisites,
+	/// otherwise keeping the order in which they were given.
+	fn sort_topologically(
+		loaded: &[(ModuleDescriptor, anyhow::Result<Module>)],
+		prerequisites: &[BTreeSet<usize>],
+	) -> anyhow::Result<Vec<usize>> {
+		let mut order = Vec::with_capacity(prerequisites.len());
+		let mut remaining: BTreeSet<usize> = (0..prerequisites.len()).collect();
+
+		while !remaining.is_empty() {
+			let next = remaining
+				.iter()
+				.copied()
+				.find(|i| prerequisites[*i].iter().all(|p| !remaining.contains(p)));
+			let Some(next) = next else {
+				let names: Vec<&str> = remaining
+					.iter()
+					.map(|i| loaded[*i].0.name.as_str())
+					.collect();
+				return Err(anyhow!(
+					"The ordering of the modules {} contains a cycle",
+					names.join(", ")
+				));
+			};
+			remaining.remove(&next);
+			order.push(next);
+		}
+
+		Ok(order)
 	}
 
 	fn load(files: &Files, mod_name: &str) -> anyhow::Result<ModuleDescriptor> {
@@ -83,36 +375,58 @@ impl ModuleManager {
 		Ok(module_descr)
 	}
 
+	/// Applies and reloads a module. The module is only skipped if none of its
+	/// inputs changed since the last application.
 	fn apply_module(
-		&self,
 		module_descr: &ModuleDescriptor,
+		module: &Module,
 		config: &Config,
 		theme: &Theme,
 		accent: Color,
 		params: &ApplyParams,
-	) {
-		heading!("{}", module_descr.name);
+		fingerprints: &Mutex<Fingerprints>,
+	) -> anyhow::Result<ModuleStatus> {
+		let mut module_config = config.for_module(&module_descr.name);
 
-		let module = match Module::load(&self.lua_runtime, &module_descr.path, params.check_deps) {
-			Ok(module) => module,
-			Err(error) => {
-				error!("{error:#}");
-				niji_console::println!();
-				return;
-			}
-		};
-
-		let mut module_config = config.global.clone();
-		if let Some(specific) = config.module_config.get(&module_descr.name) {
-			module_config.extend(specific.clone());
+		let fingerprint =
+			match module_fingerprint(&module_descr.path, &module_config, theme, accent) {
+				Ok(fingerprint) => Some(fingerprint),
+				Err(err) => {
+					error!("{err:#}");
+					None
+				}
+			};
+		// Dry runs show the changes of every module, like `--force` would
+		if let Some(fingerprint) = &fingerprint
+			&& !params.force
+			&& !params.dry_run
+			&& fingerprints
+				.lock()
+				.unwrap()
+				.is_current(&module_descr.name, fingerprint)
+		{
+			info!("No changes since the last application, skipping. Use --force to re-apply.");
+			return Ok(ModuleStatus::Skipped);
+		}
+		if !params.dry_run {
+			fingerprints.lock().unwrap().remove(&module_descr.name);
 		}
 
-		if let Err(err) = module.apply(module_config.clone(), theme.clone(), accent) {
-			error!("{err:#}");
-			error!("Aborting module execution");
-			niji_console::println!();
-			return;
+		if let Some(schema) = module.schema()? {
+			schema.validate(
+				&module_descr.name,
+				&mut module_config,
+				config.module_config.get(&module_descr.name),
+			)?;
 		}
+
+		module.apply(module_config.clone(), theme.clone(), accent)?;
+
+		let mut status = ModuleStatus::Applied;
+		// The fingerprint is only recorded once the module doesn't need to be
+		// reloaded anymore, so that a later application reloads it otherwise
+		let mut up_to_date =
+			module.manifest().reload != ReloadBehavior::Live || !module.can_reload();
 		if params.reload {
 			if config.disable_reloads.is_disabled(&module_descr.name) {
 				info!(
@@ -120,18 +434,59 @@ impl ModuleManager {
 					 restart",
 					module_descr.name
 				);
+			} else if module.manifest().reload == ReloadBehavior::Restart {
+				info!(
+					"Module {} cannot be reloaded. You will only see the changes after a restart",
+					module_descr.name
+				);
+			} else if module.manifest().reload == ReloadBehavior::None {
+				debug!("Module {} does not need to be reloaded.", module_descr.name);
 			} else if module.can_reload() {
-				info!("Reloading...");
-				if let Err(err) = module.reload(module_config) {
-					error!("{err:#}");
-					error!("Reloading of {} failed", module_descr.name);
-					niji_console::println!();
+				if params.dry_run {
+					info!("Recording the reload commands...");
+				} else {
+					info!("Reloading...");
+				}
+				module
+					.reload(module_config)
+					.context(format!("Reloading of {} failed", module_descr.name))?;
+				// Dry runs only record the commands, so nothing was reloaded
+				if !params.dry_run {
+					status = ModuleStatus::Reloaded;
 				}
+				up_to_date = true;
 			} else {
 				debug!("Module {} does not support reloading.", module_descr.name);
 			}
 		}
+
+		if let Some(dry_run) = module.take_dry_run() {
+			dry_run.print();
+			return Ok(status);
+		}
+		if let Some(fingerprint) = fingerprint
+			&& up_to_date
+		{
+			fingerprints
+				.lock()
+				.unwrap()
+				.insert(module_descr.name.clone(), fingerprint);
+		}
 		info!("Done!");
+		Ok(status)
+	}
+
+	/// Restores the files the module wrote to their previous state.
+	fn roll_back(module: &Module) {
+		let Some(transaction) = module.take_transaction() else {
+			return;
+		};
+		match transaction.rollback() {
+			Ok(0) => {}
+			Ok(1) => warn!("Restored the previous version of 1 file"),
+			Ok(count) => warn!("Restored the previous versions of {count} files"),
+			Err(err) => error!("Failed to restore the previous state: {err:#}"),
+		}
 	}
 
 	fn find_module_dir(files: &Files, name: &str) -> Option<PathBuf> {
@@ -146,38 +501,87 @@ impl ModuleManager {
 
 #[cfg(test)]
 mod tests {
-	use std::{collections::HashMap, fs};
+	use std::{collections::HashMap, fs, path::Path};
 
 	use tempfile::tempdir;
 
-	use crate::{config::DisableReloads, theme::test_utils::test_theme};
+	use crate::{config::DisableReloads, hooks::Hooks, theme::test_utils::test_theme};
 
 	use super::*;
 
 	#[test]
 	fn init() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
-		ModuleManager::new(ModuleManagerInit { xdg, files }).unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		ModuleManager::new(ModuleManagerInit { xdg, files });
+	}
+
+	#[test]
+	fn list_modules() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		let custom_dir = xdg.config_home.join("niji/modules");
+		let builtin_dir = xdg.data_dirs[0].join("niji/modules");
+		for dir in [
+			custom_dir.join("kitty"),
+			builtin_dir.join("kitty"),
+			builtin_dir.join("gtk"),
+		] {
+			fs::create_dir_all(dir).unwrap();
+		}
+
+		assert_eq!(
+			module_manager.list(),
+			vec![
+				ModuleListing {
+					name: "gtk".to_string(),
+					path: builtin_dir.join("gtk"),
+					shadowed: false,
+				},
+				ModuleListing {
+					name: "kitty".to_string(),
+					path: custom_dir.join("kitty"),
+					shadowed: false,
+				},
+				ModuleListing {
+					name: "kitty".to_string(),
+					path: builtin_dir.join("kitty"),
+					shadowed: true,
+				},
+			]
+		);
+		assert_eq!(
+			module_manager.find("kitty").unwrap(),
+			custom_dir.join("kitty")
+		);
+		module_manager.find("sway").unwrap_err();
 	}
 
 	#[test]
 	fn apply_module() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
-		let config = Rc::new(Config {
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let config = Arc::new(Config {
 			modules: vec![],
 			disable_reloads: DisableReloads::None,
 			global: HashMap::new(),
+			module_order: HashMap::new(),
+			profiles: HashMap::new(),
+			hooks: Hooks::default(),
 			module_config: HashMap::new(),
 		});
 		let module_manager = ModuleManager::new(ModuleManagerInit {
 			xdg: xdg.clone(),
 			files,
-		})
-		.unwrap();
+		});
 
 		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
 		fs::write(
@@ -194,6 +598,10 @@ mod tests {
 				&ApplyParams {
 					reload: false,
 					check_deps: true,
+					force: false,
+					dry_run: false,
+					fail_fast: false,
+					keep_partial: false,
 				},
 				&["test".to_string()],
 			)
@@ -201,21 +609,478 @@ mod tests {
 	}
 
 	#[test]
-	fn apply_module_error() {
+	fn skip_unchanged_module() {
 		let tempdir = tempdir().unwrap();
-		let xdg = Rc::new(XdgDirs::in_tempdir(&tempdir));
-		let files = Rc::new(Files::new(&xdg).unwrap());
-		let config = Rc::new(Config {
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let mut config = Config::default();
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		let counter_file = tempdir.path().join("counter");
+		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/test/module.lua"),
+			format!(
+				"return {{ apply = function() local f = io.open({:?}, 'a'); f:write('x'); \
+				 f:close() end }}",
+				counter_file.display()
+			),
+		)
+		.unwrap();
+
+		let apply = |config: &Config, force: bool| {
+			module_manager
+				.apply(
+					config,
+					&test_theme(),
+					Color::BLACK,
+					&ApplyParams {
+						reload: false,
+						check_deps: true,
+						force,
+						dry_run: false,
+						fail_fast: false,
+						keep_partial: false,
+					},
+					&["test".to_string()],
+				)
+				.unwrap();
+			fs::read_to_string(&counter_file).unwrap().len()
+		};
+
+		assert_eq!(apply(&config, false), 1);
+		assert_eq!(apply(&config, false), 1);
+		assert_eq!(apply(&config, true), 2);
+
+		config.global.insert(
+			"font_family".to_string(),
+			crate::config::ModuleConfigValue::String("Fira Sans".to_string()),
+		);
+		assert_eq!(apply(&config, false), 3);
+		assert_eq!(apply(&config, false), 3);
+	}
+
+	#[test]
+	fn reload_after_no_reload() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/test/module.lua"),
+			"return { apply = function() end, reload = function() end }",
+		)
+		.unwrap();
+
+		let apply = |reload: bool| {
+			module_manager
+				.apply(
+					&Config::default(),
+					&test_theme(),
+					Color::BLACK,
+					&ApplyParams {
+						reload,
+						check_deps: true,
+						force: false,
+						dry_run: false,
+						fail_fast: false,
+						keep_partial: false,
+					},
+					&["test".to_string()],
+				)
+				.unwrap()
+				.modules[0]
+				.status
+		};
+
+		assert_eq!(apply(false), ModuleStatus::Applied);
+		assert_eq!(apply(true), ModuleStatus::Reloaded);
+		assert_eq!(apply(true), ModuleStatus::Skipped);
+	}
+
+	fn write_logging_module(xdg: &XdgDirs, log_file: &Path, name: &str, manifest: &str) {
+		let module_dir = xdg.config_home.join("niji/modules").join(name);
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			format!(
+				"return {{ apply = function() local f = io.open({:?}, 'a'); f:write('{name} '); \
+				 f:close() end }}",
+				log_file.display()
+			),
+		)
+		.unwrap();
+		fs::write(module_dir.join("module.toml"), manifest).unwrap();
+	}
+
+	#[test]
+	fn apply_in_order() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		let log_file = tempdir.path().join("log");
+		write_logging_module(&xdg, &log_file, "waybar", "after = ['sway']");
+		write_logging_module(&xdg, &log_file, "sway", "");
+		write_logging_module(&xdg, &log_file, "gtk", "before = ['sway']");
+		write_logging_module(&xdg, &log_file, "unused", "before = ['gtk']");
+
+		let apply = |config: &Config| {
+			fs::write(&log_file, "").unwrap();
+			module_manager.apply(
+				config,
+				&test_theme(),
+				Color::BLACK,
+				&ApplyParams {
+					reload: false,
+					check_deps: true,
+					force: true,
+					dry_run: false,
+					fail_fast: false,
+					keep_partial: false,
+				},
+				&["waybar".to_string(), "sway".to_string(), "gtk".to_string()],
+			)?;
+			anyhow::Ok(fs::read_to_string(&log_file).unwrap())
+		};
+
+		let mut config = Config::default();
+		assert_eq!(apply(&config).unwrap(), "gtk sway waybar ");
+
+		config.module_order.insert(
+			"gtk".to_string(),
+			toml::from_str("before = []\nafter = ['waybar']").unwrap(),
+		);
+		assert_eq!(apply(&config).unwrap(), "sway waybar gtk ");
+
+		config.module_order.insert(
+			"sway".to_string(),
+			toml::from_str("after = ['gtk']").unwrap(),
+		);
+		apply(&config).unwrap_err();
+	}
+
+	#[test]
+	fn apply_concurrently() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		// Each module waits for the other one to start, which only succeeds if
+		// they are applied at the same time.
+		for (name, other) in [("foot", "kitty"), ("kitty", "foot")] {
+			let module_dir = xdg.config_home.join("niji/modules").join(name);
+			fs::create_dir_all(&module_dir).unwrap();
+			fs::write(
+				module_dir.join("module.lua"),
+				format!(
+					r#"
+					return {{ apply = function()
+						io.open({started:?}, "w"):close()
+						local deadline = os.time() + 10
+						while os.time() < deadline do
+							local file = io.open({other_started:?})
+							if file then
+								file:close()
+								io.open({done:?}, "w"):close()
+								return
+							end
+						end
+					end }}
+					"#,
+					started = tempdir.path().join(name).display().to_string(),
+					other_started = tempdir.path().join(other).display().to_string(),
+					done = tempdir
+						.path()
+						.join(format!("{name}.done"))
+						.display()
+						.to_string(),
+				),
+			)
+			.unwrap();
+		}
+
+		module_manager
+			.apply(
+				&Config::default(),
+				&test_theme(),
+				Color::BLACK,
+				&ApplyParams {
+					reload: false,
+					check_deps: true,
+					force: true,
+					dry_run: false,
+					fail_fast: false,
+					keep_partial: false,
+				},
+				&["foot".to_string(), "kitty".to_string()],
+			)
+			.unwrap();
+
+		assert!(tempdir.path().join("foot.done").exists());
+		assert!(tempdir.path().join("kitty.done").exists());
+	}
+
+	#[test]
+	fn skip_dependents_of_failed_modules() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
+
+		let log_file = tempdir.path().join("log");
+		fs::create_dir_all(xdg.config_home.join("niji/modules/hyprland")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/hyprland/module.lua"),
+			"return { apply = function() error('oops') end }",
+		)
+		.unwrap();
+		write_logging_module(&xdg, &log_file, "hyprpaper", "after = ['hyprland']");
+		write_logging_module(&xdg, &log_file, "swaylock", "after = ['hyprpaper']");
+		write_logging_module(&xdg, &log_file, "kitty", "");
+
+		let apply = |fail_fast: bool| {
+			fs::write(&log_file, "").unwrap();
+			let report = module_manager
+				.apply(
+					&Config::default(),
+					&test_theme(),
+					Color::BLACK,
+					&ApplyParams {
+						reload: false,
+						check_deps: true,
+						force: true,
+						dry_run: false,
+						fail_fast,
+						keep_partial: false,
+					},
+					&[
+						"swaylock".to_string(),
+						"hyprpaper".to_string(),
+						"hyprland".to_string(),
+						"kitty".to_string(),
+					],
+				)
+				.unwrap();
+			let statuses: Vec<(String, ModuleStatus)> = report
+				.modules
+				.into_iter()
+				.map(|module| (module.name, module.status))
+				.collect();
+			(fs::read_to_string(&log_file).unwrap(), statuses)
+		};
+
+		let (log, statuses) = apply(false);
+		assert_eq!(log, "kitty ");
+		assert_eq!(
+			statuses,
+			vec![
+				("hyprland".to_string(), ModuleStatus::Failed),
+				("hyprpaper".to_string(), ModuleStatus::Skipped),
+				("swaylock".to_string(), ModuleStatus::Skipped),
+				("kitty".to_string(), ModuleStatus::Applied),
+			]
+		);
+
+		let (log, statuses) = apply(true);
+		assert_eq!(log, "");
+		assert_eq!(statuses[3], ("kitty".to_string(), ModuleStatus::Skipped));
+	}
+
+	#[test]
+	fn dry_run() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let config = Arc::new(Config {
+			modules: vec![],
+			disable_reloads: DisableReloads::None,
+			global: HashMap::new(),
+			module_order: HashMap::new(),
+			profiles: HashMap::new(),
+			hooks: Hooks::default(),
+			module_config: HashMap::new(),
+		});
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files: files.clone(),
+		});
+
+		let marker = tempdir.path().join("reloaded");
+		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/test/module.lua"),
+			format!(
+				r#"
+					return {{
+						apply = function()
+							niji.fs.write_config("test/test.conf", "test")
+							niji.fs.output_unchecked("test.conf", "test")
+						end,
+						reload = function()
+							os.execute("touch '{0}'")
+							io.popen("touch '{0}'"):close()
+							niji.os.exec_detached("touch '{0}'")
+						end
+					}}
+				"#,
+				marker.display()
+			),
+		)
+		.unwrap();
+
+		module_manager
+			.apply(
+				&config,
+				&test_theme(),
+				Color::BLACK,
+				&ApplyParams {
+					reload: true,
+					check_deps: true,
+					force: false,
+					dry_run: true,
+					fail_fast: false,
+					keep_partial: false,
+				},
+				&["test".to_string()],
+			)
+			.unwrap();
+
+		assert!(!xdg.config_home.join("test").exists());
+		assert!(!files.output_dir().join("test").exists());
+		assert!(!files.fingerprints_file().exists());
+		assert!(!marker.exists());
+
+		// Unchanged modules aren't skipped in dry runs
+		let apply = |dry_run: bool| {
+			module_manager
+				.apply(
+					&config,
+					&test_theme(),
+					Color::BLACK,
+					&ApplyParams {
+						reload: true,
+						check_deps: true,
+						force: false,
+						dry_run,
+						fail_fast: false,
+						keep_partial: false,
+					},
+					&["test".to_string()],
+				)
+				.unwrap()
+				.modules[0]
+				.status
+		};
+		assert_eq!(apply(false), ModuleStatus::Reloaded);
+		assert_eq!(apply(false), ModuleStatus::Skipped);
+		// The reload commands are only recorded in dry runs
+		assert_eq!(apply(true), ModuleStatus::Applied);
+	}
+
+	#[test]
+	fn roll_back_failed_module() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let config = Arc::new(Config {
 			modules: vec![],
 			disable_reloads: DisableReloads::None,
 			global: HashMap::new(),
+			module_order: HashMap::new(),
+			profiles: HashMap::new(),
+			hooks: Hooks::default(),
 			module_config: HashMap::new(),
 		});
 		let module_manager = ModuleManager::new(ModuleManagerInit {
 			xdg: xdg.clone(),
 			files,
-		})
+		});
+
+		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
+		fs::write(
+			xdg.config_home.join("niji/modules/test/module.lua"),
+			r#"
+				return {
+					apply = function()
+						niji.fs.write_config("existing.conf", "new")
+						niji.fs.write_config("test/new.conf", "new")
+						error("oops")
+					end
+				}
+			"#,
+		)
 		.unwrap();
+		let existing = xdg.config_home.join("existing.conf");
+		crate::managed_fs::write(&existing, "old").unwrap();
+
+		let apply = |keep_partial: bool| {
+			module_manager
+				.apply(
+					&config,
+					&test_theme(),
+					Color::BLACK,
+					&ApplyParams {
+						reload: false,
+						check_deps: true,
+						force: true,
+						dry_run: false,
+						fail_fast: false,
+						keep_partial,
+					},
+					&["test".to_string()],
+				)
+				.unwrap();
+		};
+
+		apply(false);
+		assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+		assert!(!xdg.config_home.join("test").exists());
+		// The file is still managed by niji, so writing it doesn't prompt
+		assert!(!crate::managed_fs::would_prompt(&existing));
+
+		apply(true);
+		assert_eq!(fs::read_to_string(&existing).unwrap(), "new");
+		assert!(xdg.config_home.join("test/new.conf").exists());
+	}
+
+	#[test]
+	fn apply_module_error() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let config = Arc::new(Config {
+			modules: vec![],
+			disable_reloads: DisableReloads::None,
+			global: HashMap::new(),
+			module_order: HashMap::new(),
+			profiles: HashMap::new(),
+			hooks: Hooks::default(),
+			module_config: HashMap::new(),
+		});
+		let module_manager = ModuleManager::new(ModuleManagerInit {
+			xdg: xdg.clone(),
+			files,
+		});
 
 		fs::create_dir_all(xdg.config_home.join("niji/modules/test")).unwrap();
 		fs::write(
@@ -224,8 +1089,8 @@ mod tests {
 		)
 		.unwrap();
 
-		// This should not error, instead there should be a log message
-		module_manager
+		// This should not error, instead the failure should be reported
+		let report = module_manager
 			.apply(
 				&config,
 				&test_theme(),
@@ -233,9 +1098,15 @@ mod tests {
 				&ApplyParams {
 					reload: false,
 					check_deps: true,
+					force: false,
+					dry_run: false,
+					fail_fast: false,
+					keep_partial: false,
 				},
 				&["test".to_string()],
 			)
 			.unwrap();
+		assert_eq!(report.modules[0].status, ModuleStatus::Failed);
+		report.check().unwrap_err();
 	}
 }
@@ -0,0 +1,244 @@
+use std::{env, fmt, fs, path::Path};
+
+use anyhow::{Context, anyhow};
+use log::warn;
+use serde::Deserialize;
+
+use crate::{
+	config::ModuleOrder, dependency::Dependency, lua::api::API_VERSION, schema::ConfigSchema,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadBehavior {
+	/// The module is reloaded using its reload function, if it has one.
+	#[default]
+	Live,
+
+	/// The changes only take effect after the application is restarted.
+	Restart,
+
+	/// The application picks up the changes by itself.
+	None,
+}
+
+impl fmt::Display for ReloadBehavior {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Live => write!(f, "live"),
+			Self::Restart => write!(f, "restart"),
+			Self::None => write!(f, "none"),
+		}
+	}
+}
+
+/// The contents of a module's `module.toml`, which describe the module
+/// without having to execute its lua code.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModuleManifest {
+	pub name: Option<String>,
+	pub description: Option<String>,
+	pub version: Option<String>,
+	pub author: Option<String>,
+	pub api_version: Option<u32>,
+	pub dependencies: Vec<Dependency>,
+
+	/// Dependencies that only cause a warning if they are missing.
+	pub optional_dependencies: Vec<Dependency>,
+	pub desktops: Vec<String>,
+	pub schema: Option<ConfigSchema>,
+	pub reload: ReloadBehavior,
+
+	/// The modules this module has to be applied before, if they are applied.
+	pub before: Vec<String>,
+
+	/// The modules this module has to be applied after, if they are applied.
+	pub after: Vec<String>,
+}
+
+impl ModuleManifest {
+	pub const FILE: &'static str = "module.toml";
+
+	/// Older modules only list their dependencies in this file.
+	const DEPS_FILE: &'static str = "deps.txt";
+
+	pub fn load(module_dir: &Path) -> anyhow::Result<Self> {
+		let manifest_file = module_dir.join(Self::FILE);
+		if manifest_file.exists() {
+			let source = fs::read_to_string(&manifest_file)
+				.context(format!("Failed to read {}", manifest_file.display()))?;
+			return toml::from_str(&source).context(format!(
+				"Invalid module manifest {}",
+				manifest_file.display()
+			));
+		}
+
+		let deps_file = module_dir.join(Self::DEPS_FILE);
+		if deps_file.exists() {
+			let dependencies = fs::read_to_string(&deps_file)
+				.context(format!("Failed to read {}", deps_file.display()))?
+				.lines()
+				.map(str::trim)
+				.filter(|line| !line.is_empty())
+				.map(str::parse)
+				.collect::<anyhow::Result<_>>()
+				.context(format!("Invalid dependency in {}", deps_file.display()))?;
+
+			return Ok(Self {
+				dependencies,
+				..Self::default()
+			});
+		}
+
+		Ok(Self::default())
+	}
+
+	pub fn order(&self) -> ModuleOrder {
+		ModuleOrder {
+			before: Some(self.before.clone()),
+			after: Some(self.after.clone()),
+		}
+	}
+
+	/// Checks whether the module works with this version of niji, and warns
+	/// if it doesn't support the current desktop.
+	pub fn check_compatibility(&self, module_name: &str) -> anyhow::Result<()> {
+		if let Some(name) = &self.name
+			&& name != module_name
+		{
+			warn!("The manifest of module {module_name} declares a different name: {name}");
+		}
+
+		if let Some(api_version) = self.api_version
+			&& api_version > API_VERSION
+		{
+			return Err(anyhow!(
+				"Module {module_name} requires version {api_version} of the niji API, but this \
+				 version of niji only supports version {API_VERSION}. Try updating niji."
+			));
+		}
+
+		if !self.desktops.is_empty()
+			&& let Ok(current_desktops) = env::var("XDG_CURRENT_DESKTOP")
+			&& !self.supports_desktop(&current_desktops)
+		{
+			warn!(
+				"Module {module_name} only supports the desktops {}, but the current desktop is \
+				 {current_desktops}",
+				self.desktops.join(", ")
+			);
+		}
+
+		Ok(())
+	}
+
+	/// `current_desktops` is a colon-separated list, as in
+	/// `XDG_CURRENT_DESKTOP`.
+	fn supports_desktop(&self, current_desktops: &str) -> bool {
+		current_desktops.split(':').any(|current| {
+			self.desktops
+				.iter()
+				.any(|desktop| desktop.eq_ignore_ascii_case(current))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	#[test]
+	fn load_manifest() {
+		let tempdir = tempdir().unwrap();
+		fs::write(
+			tempdir.path().join("module.toml"),
+			r#"
+				name = "test"
+				description = "A test module"
+				version = "1.2.0"
+				api_version = 1
+				dependencies = ["foo", "bar | baz"]
+				optional_dependencies = [{ program = "qux", min_version = "2.1" }]
+				desktops = ["sway"]
+				reload = "restart"
+				after = ["sway"]
+
+				[schema]
+				show_shadow = { type = "bool", default = true }
+			"#,
+		)
+		.unwrap();
+
+		let manifest = ModuleManifest::load(tempdir.path()).unwrap();
+
+		assert_eq!(manifest.description.as_deref(), Some("A test module"));
+		assert_eq!(manifest.version.as_deref(), Some("1.2.0"));
+		assert_eq!(manifest.dependencies.len(), 2);
+		assert_eq!(manifest.dependencies[1].to_string(), "bar | baz");
+		assert_eq!(manifest.optional_dependencies[0].to_string(), "qux >= 2.1");
+		assert_eq!(manifest.reload, ReloadBehavior::Restart);
+		assert_eq!(
+			manifest.order(),
+			ModuleOrder {
+				before: Some(vec![]),
+				after: Some(vec!["sway".to_string()]),
+			}
+		);
+		assert!(
+			manifest
+				.schema
+				.as_ref()
+				.unwrap()
+				.0
+				.contains_key("show_shadow")
+		);
+		assert!(manifest.supports_desktop("SWAY"));
+		assert!(manifest.supports_desktop("GNOME:sway"));
+		assert!(!manifest.supports_desktop("Hyprland"));
+		manifest.check_compatibility("test").unwrap();
+	}
+
+	#[test]
+	fn load_deps_file() {
+		let tempdir = tempdir().unwrap();
+		fs::write(tempdir.path().join("deps.txt"), "foo\nbar\n\n").unwrap();
+
+		let manifest = ModuleManifest::load(tempdir.path()).unwrap();
+
+		assert_eq!(
+			manifest
+				.dependencies
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			vec!["foo".to_string(), "bar".to_string()]
+		);
+		assert_eq!(manifest.reload, ReloadBehavior::Live);
+	}
+
+	#[test]
+	fn builtin_manifests() {
+		let builtin_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets/modules");
+		for entry in fs::read_dir(builtin_dir).unwrap() {
+			let module_dir = entry.unwrap().path();
+			let manifest = ModuleManifest::load(&module_dir).unwrap();
+			assert!(manifest.description.is_some(), "{}", module_dir.display());
+			manifest
+				.check_compatibility(&module_dir.file_name().unwrap().to_string_lossy())
+				.unwrap();
+		}
+	}
+
+	#[test]
+	fn incompatible_api_version() {
+		let manifest = ModuleManifest {
+			api_version: Some(API_VERSION + 1),
+			..ModuleManifest::default()
+		};
+
+		manifest.check_compatibility("test").unwrap_err();
+	}
+}
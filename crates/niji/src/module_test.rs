@@ -0,0 +1,526 @@
+use std::{
+	collections::BTreeMap,
+	env,
+	fmt::Write,
+	fs,
+	path::{Component, Path, PathBuf},
+	sync::Arc,
+};
+
+use anyhow::{Context, anyhow};
+use log::error;
+use serde::Deserialize;
+use similar::TextDiff;
+use tempfile::TempDir;
+
+use crate::{
+	config::ModuleConfig,
+	dry_run::DryRun,
+	files::Files,
+	lua::runtime::{LuaRuntime, LuaRuntimeInit},
+	module::Module,
+	module_manifest::ReloadBehavior,
+	theme::{ColorRef, Theme},
+	theme_manager::ThemeManager,
+	utils::xdg::XdgDirs,
+};
+
+fn default_accent() -> String {
+	"blue".to_string()
+}
+
+/// A test case in the `tests` directory of a module, which sets the accent
+/// color and module config to apply the module with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestCase {
+	#[serde(skip)]
+	name: String,
+
+	#[serde(default = "default_accent")]
+	accent: String,
+
+	#[serde(default)]
+	config: ModuleConfig,
+
+	/// Files to create in the config directory of the sandbox, like the
+	/// application's own config, keyed by their path relative to it.
+	#[serde(default)]
+	files: BTreeMap<PathBuf, String>,
+}
+
+impl TestCase {
+	/// Checks that the files are relative paths, so that they end up inside
+	/// the sandbox.
+	fn check_files(&self) -> anyhow::Result<()> {
+		for path in self.files.keys() {
+			let components_valid = path
+				.components()
+				.all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+			let has_name = path.components().any(|c| matches!(c, Component::Normal(_)));
+			if !components_valid || !has_name {
+				return Err(anyhow!(
+					"Invalid file \"{}\"; files must be relative to the config directory",
+					path.display()
+				));
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Default for TestCase {
+	fn default() -> Self {
+		Self {
+			name: "default".to_string(),
+			accent: default_accent(),
+			config: ModuleConfig::new(),
+			files: BTreeMap::new(),
+		}
+	}
+}
+
+/// A temporary directory that takes the place of the user's home directory.
+struct Sandbox {
+	dir: TempDir,
+	xdg: Arc<XdgDirs>,
+	files: Arc<Files>,
+}
+
+impl Sandbox {
+	fn new(xdg: &XdgDirs) -> anyhow::Result<Self> {
+		let dir = tempfile::tempdir().context("Failed to create the test sandbox")?;
+		let xdg = Arc::new(xdg.sandboxed(dir.path()));
+		let files = Arc::new(Files::new(&xdg)?);
+		Ok(Self { dir, xdg, files })
+	}
+}
+
+/// Applies a module in a sandbox, and compares the files it writes and the
+/// commands it runs to the snapshots in its `tests/snapshots` directory.
+pub struct ModuleTester {
+	name: String,
+	module_dir: PathBuf,
+	xdg: XdgDirs,
+	home: Option<PathBuf>,
+}
+
+impl ModuleTester {
+	const TESTS_DIR: &'static str = "tests";
+	const SNAPSHOTS_DIR: &'static str = "snapshots";
+
+	pub fn new(xdg: &XdgDirs, module_dir: &Path) -> Self {
+		Self {
+			name: module_dir
+				.file_name()
+				.unwrap_or_default()
+				.to_string_lossy()
+				.into_owned(),
+			module_dir: module_dir.to_path_buf(),
+			xdg: xdg.clone(),
+			home: env::var_os("HOME")
+				.filter(|home| home.len() > 1)
+				.map(PathBuf::from),
+		}
+	}
+
+	/// Returns the themes that are available in the sandbox, which are the
+	/// built-in ones.
+	pub fn builtin_themes(&self) -> anyhow::Result<Vec<Theme>> {
+		let sandbox = Sandbox::new(&self.xdg)?;
+		let theme_manager = ThemeManager::new(Arc::clone(&sandbox.files));
+		theme_manager
+			.list_themes()
+			.iter()
+			.map(|name| theme_manager.get_theme(name))
+			.collect()
+	}
+
+	/// Runs every test case with every theme. With `update`, the snapshots
+	/// are rewritten instead of compared.
+	pub fn run(&self, themes: &[Theme], update: bool) -> anyhow::Result<()> {
+		let cases = self.load_cases()?;
+		let total = cases.len() * themes.len();
+		let mut failures = 0;
+
+		for case in &cases {
+			for theme in themes {
+				if !self.run_case(case, theme, update) {
+					failures += 1;
+				}
+			}
+		}
+
+		if failures == 0 {
+			return Ok(());
+		}
+		Err(anyhow!(
+			"{failures} of {total} tests of module {} failed. If the changes are intended, run \
+			 `niji module test --update {}` to update the snapshots.",
+			self.name,
+			self.name
+		))
+	}
+
+	fn load_cases(&self) -> anyhow::Result<Vec<TestCase>> {
+		let tests_dir = self.module_dir.join(Self::TESTS_DIR);
+		let Ok(entries) = fs::read_dir(&tests_dir) else {
+			return Ok(vec![TestCase::default()]);
+		};
+
+		let mut cases = Vec::new();
+		for entry in entries {
+			let path = entry?.path();
+			if path.extension().is_none_or(|ext| ext != "toml") {
+				continue;
+			}
+			let source =
+				fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+			let mut case: TestCase =
+				toml::from_str(&source).context(format!("Invalid test case {}", path.display()))?;
+			case.name = path
+				.file_stem()
+				.unwrap_or_default()
+				.to_string_lossy()
+				.into_owned();
+			case.check_files()
+				.context(format!("Invalid test case {}", path.display()))?;
+			cases.push(case);
+		}
+
+		if cases.is_empty() {
+			cases.push(TestCase::default());
+		}
+		cases.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(cases)
+	}
+
+	/// Returns whether the test passed.
+	fn run_case(&self, case: &TestCase, theme: &Theme, update: bool) -> bool {
+		let label = format!("{} / {}", case.name, theme.name);
+		let (result, output) =
+			niji_console::api::capture(&label, || self.take_snapshot(case, theme));
+		let snapshot = match result {
+			Ok(snapshot) => snapshot,
+			Err(err) => {
+				let _ = niji_console::api::print_captured(&output);
+				error!("{label}: {err:#}");
+				return false;
+			}
+		};
+
+		let path = self.snapshot_path(case, theme);
+		let existing = fs::read_to_string(&path).ok();
+		if existing.as_deref() == Some(snapshot.as_str()) {
+			niji_console::println!("ok       {label}");
+			return true;
+		}
+
+		if update {
+			if let Err(err) = Self::write_snapshot(&path, &snapshot) {
+				error!("{err:#}");
+				return false;
+			}
+			niji_console::println!("updated  {label}");
+			return true;
+		}
+
+		let Some(existing) = existing else {
+			niji_console::println!("missing  {label}");
+			return false;
+		};
+		niji_console::println!("changed  {label}");
+		niji_console::println!(
+			"{}",
+			TextDiff::from_lines(&existing, &snapshot)
+				.unified_diff()
+				.header(&path.to_string_lossy(), "actual")
+		);
+		false
+	}
+
+	fn write_snapshot(path: &Path, snapshot: &str) -> anyhow::Result<()> {
+		let dir = path
+			.parent()
+			.context(format!("Invalid snapshot path {}", path.display()))?;
+		fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+		fs::write(path, snapshot).context(format!("Failed to write {}", path.display()))
+	}
+
+	fn snapshot_path(&self, case: &TestCase, theme: &Theme) -> PathBuf {
+		self.module_dir
+			.join(Self::TESTS_DIR)
+			.join(Self::SNAPSHOTS_DIR)
+			.join(&case.name)
+			.join(format!("{}.snap", theme.name))
+	}
+
+	/// Applies and reloads the module in dry-run mode in a new sandbox, and
+	/// returns the recorded changes.
+	fn take_snapshot(&self, case: &TestCase, theme: &Theme) -> anyhow::Result<String> {
+		let sandbox = Sandbox::new(&self.xdg)?;
+		for (path, content) in &case.files {
+			let path = sandbox.xdg.config_home.join(path);
+			let dir = path
+				.parent()
+				.context(format!("Invalid file path {}", path.display()))?;
+			fs::create_dir_all(dir).context(format!("Failed to create {}", dir.display()))?;
+			fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+		}
+
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: Arc::clone(&sandbox.xdg),
+			files: Arc::clone(&sandbox.files),
+		})
+		.context("Failed to initialize lua runtime")?;
+		runtime.enable_dry_run();
+		let module = Module::load(runtime, &self.module_dir, false)?;
+
+		let mut config = case.config.clone();
+		if let Some(schema) = module.schema()? {
+			schema.validate(&self.name, &mut config, Some(&case.config))?;
+		}
+		let accent = ColorRef::named(&case.accent)
+			.resolve(&theme.palette)
+			.context("Invalid accent color")?;
+
+		module.apply(config.clone(), theme.clone(), accent)?;
+		if module.manifest().reload == ReloadBehavior::Live && module.can_reload() {
+			module.reload(config)?;
+		}
+
+		let dry_run = module.take_dry_run().unwrap_or_default();
+		Ok(self.format_snapshot(&sandbox, &dry_run))
+	}
+
+	/// Formats the recorded changes, replacing the paths that differ between
+	/// machines with placeholders.
+	fn format_snapshot(&self, sandbox: &Sandbox, dry_run: &DryRun) -> String {
+		let mut snapshot = String::new();
+		for (path, content) in dry_run.writes() {
+			let _ = writeln!(snapshot, "==> {} <==", path.display());
+			snapshot.push_str(content);
+			if !content.ends_with('\n') {
+				snapshot.push('\n');
+			}
+			snapshot.push('\n');
+		}
+		if !dry_run.removals().is_empty() {
+			snapshot.push_str("==> removed <==\n");
+			for path in dry_run.removals() {
+				let _ = writeln!(snapshot, "{}", path.display());
+			}
+			snapshot.push('\n');
+		}
+		if !dry_run.renames().is_empty() {
+			snapshot.push_str("==> renamed <==\n");
+			for (from, to) in dry_run.renames() {
+				let _ = writeln!(snapshot, "{} -> {}", from.display(), to.display());
+			}
+			snapshot.push('\n');
+		}
+		if !dry_run.commands().is_empty() {
+			snapshot.push_str("==> commands <==\n");
+			for command in dry_run.commands() {
+				let _ = writeln!(snapshot, "{command}");
+			}
+		}
+
+		// The module directory comes first, since it may be inside of the home
+		// directory
+		let mut replacements = vec![
+			(self.module_dir.clone(), "$MODULE_DIR"),
+			(sandbox.xdg.config_home.clone(), "$XDG_CONFIG_HOME"),
+			(sandbox.xdg.data_home.clone(), "$XDG_DATA_HOME"),
+			(sandbox.xdg.state_home.clone(), "$XDG_STATE_HOME"),
+			(sandbox.xdg.cache_home.clone(), "$XDG_CACHE_HOME"),
+			(sandbox.dir.path().to_path_buf(), "$SANDBOX"),
+		];
+		if let Some(home) = &self.home {
+			replacements.push((home.clone(), "~"));
+		}
+		for (path, placeholder) in replacements {
+			snapshot = snapshot.replace(path.to_string_lossy().as_ref(), placeholder);
+		}
+		snapshot
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+	use crate::{scaffold, theme::test_utils::test_theme};
+
+	#[test]
+	fn snapshots() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		scaffold::create_module(&module_dir, "test").unwrap();
+		fs::write(
+			module_dir.join("tests/light.toml"),
+			"accent = \"red\"\n[config]\n",
+		)
+		.unwrap();
+		let themes = [test_theme()];
+
+		let tester = ModuleTester::new(&xdg, &module_dir);
+		assert!(tester.builtin_themes().unwrap().is_empty());
+		tester.run(&themes, false).unwrap_err();
+		tester.run(&themes, true).unwrap();
+		tester.run(&themes, false).unwrap();
+
+		let snapshot =
+			fs::read_to_string(module_dir.join("tests/snapshots/default/test_theme.snap")).unwrap();
+		assert!(snapshot.starts_with("==> $XDG_CONFIG_HOME/test/niji-colors.conf <==\n"));
+		assert!(
+			module_dir
+				.join("tests/snapshots/light/test_theme.snap")
+				.exists()
+		);
+
+		let template = module_dir.join("colors.mustache");
+		let mut source = fs::read_to_string(&template).unwrap();
+		source.push_str("extra = {{accent}}\n");
+		fs::write(&template, source).unwrap();
+		tester.run(&themes, false).unwrap_err();
+	}
+
+	#[test]
+	fn record_commands() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			r#"
+				return {
+					apply = function(config, theme, accent)
+						niji.fs.output_unchecked("out.txt", config.text .. niji.fs.read_config("app/config"))
+					end,
+					reload = function() os.execute("test-reload") end,
+				}
+			"#,
+		)
+		.unwrap();
+		fs::create_dir_all(module_dir.join("tests")).unwrap();
+		fs::write(
+			module_dir.join("tests/text.toml"),
+			"[config]\ntext = \"hello\"\n[files]\n\"app/config\" = \" world\"\n",
+		)
+		.unwrap();
+
+		let tester = ModuleTester::new(&xdg, &module_dir);
+		tester.run(&[test_theme()], true).unwrap();
+
+		assert_eq!(
+			fs::read_to_string(module_dir.join("tests/snapshots/text/test_theme.snap")).unwrap(),
+			"==> $XDG_DATA_HOME/niji/test/out.txt <==\nhello world\n\n==> commands \
+			 <==\ntest-reload\n"
+		);
+	}
+
+	#[test]
+	fn reject_invalid_files() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		scaffold::create_module(&module_dir, "test").unwrap();
+		let tester = ModuleTester::new(&xdg, &module_dir);
+
+		for path in ["", "/", "/etc/passwd", "../escape", "."] {
+			fs::write(
+				module_dir.join("tests/default.toml"),
+				format!("[files]\n{path:?} = \"\"\n"),
+			)
+			.unwrap();
+			tester.run(&[test_theme()], true).unwrap_err();
+		}
+	}
+
+	#[test]
+	fn module_in_home() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			r#"
+				return {
+					apply = function()
+						niji.fs.output_unchecked("out.txt", niji.mod.path)
+					end,
+				}
+			"#,
+		)
+		.unwrap();
+
+		let tester = ModuleTester {
+			home: Some(tempdir.path().to_path_buf()),
+			..ModuleTester::new(&xdg, &module_dir)
+		};
+		tester.run(&[test_theme()], true).unwrap();
+
+		assert_eq!(
+			fs::read_to_string(module_dir.join("tests/snapshots/default/test_theme.snap")).unwrap(),
+			"==> $XDG_DATA_HOME/niji/test/out.txt <==\n$MODULE_DIR\n\n"
+		);
+	}
+
+	#[test]
+	fn sandbox_isolation() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let module_dir = xdg.config_home.join("niji/modules/test");
+		let outside = tempdir.path().join("outside");
+		fs::create_dir_all(&module_dir).unwrap();
+		fs::create_dir_all(&outside).unwrap();
+		fs::write(outside.join("victim.txt"), "victim").unwrap();
+		fs::write(outside.join("old.txt"), "old").unwrap();
+		fs::write(
+			module_dir.join("module.lua"),
+			format!(
+				r#"
+					local outside = "{}"
+					return {{
+						apply = function()
+							local file = io.open(outside .. "/written.txt", "w")
+							file:write("written")
+							file:close()
+							io.output(outside .. "/output.txt")
+							io.write("output")
+							io.close()
+							os.remove(outside .. "/victim.txt")
+							os.rename(outside .. "/old.txt", outside .. "/new.txt")
+						end,
+					}}
+				"#,
+				outside.display()
+			),
+		)
+		.unwrap();
+
+		let tester = ModuleTester {
+			home: Some(tempdir.path().to_path_buf()),
+			..ModuleTester::new(&xdg, &module_dir)
+		};
+		tester.run(&[test_theme()], true).unwrap();
+
+		let mut entries = fs::read_dir(&outside)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect::<Vec<_>>();
+		entries.sort();
+		assert_eq!(entries, ["old.txt", "victim.txt"]);
+		assert_eq!(
+			fs::read_to_string(module_dir.join("tests/snapshots/default/test_theme.snap")).unwrap(),
+			"==> ~/outside/written.txt <==\nwritten\n\n==> ~/outside/output.txt \
+			 <==\noutput\n\n==> removed <==\n~/outside/victim.txt\n\n==> renamed \
+			 <==\n~/outside/old.txt -> ~/outside/new.txt\n\n"
+		);
+	}
+}
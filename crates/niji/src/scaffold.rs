@@ -0,0 +1,94 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, anyhow};
+
+use crate::lua::api::API_VERSION;
+
+/// The files of a new module, relative to the module directory. `$MODULE_NAME`
+/// and `$API_VERSION` are replaced when the module is created.
+const FILES: &[(&str, &str)] = &[
+	("module.lua", include_str!("scaffold/module.lua")),
+	("module.toml", include_str!("scaffold/module.toml")),
+	("colors.mustache", include_str!("scaffold/colors.mustache")),
+	(
+		"tests/default.toml",
+		include_str!("scaffold/tests/default.toml"),
+	),
+];
+
+fn check_name(name: &str) -> anyhow::Result<()> {
+	if name.is_empty()
+		|| name.starts_with('.')
+		|| name.contains(|c: char| c == '/' || c.is_whitespace())
+	{
+		return Err(anyhow!("\"{name}\" is not a valid module name"));
+	}
+	Ok(())
+}
+
+/// Creates a new module called `name` in `dir`, which must not exist yet.
+pub fn create_module(dir: &Path, name: &str) -> anyhow::Result<()> {
+	check_name(name)?;
+	if dir.exists() {
+		return Err(anyhow!("{} already exists", dir.display()));
+	}
+
+	for (path, content) in FILES {
+		let path = dir.join(path);
+		let content = content
+			.replace("$MODULE_NAME", name)
+			.replace("$API_VERSION", &API_VERSION.to_string());
+		fs::create_dir_all(path.parent().unwrap())
+			.context(format!("Failed to create {}", dir.display()))?;
+		fs::write(&path, content).context(format!("Failed to write {}", path.display()))?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use tempfile::tempdir;
+
+	use super::*;
+	use crate::{
+		config::ModuleConfig,
+		files::Files,
+		lua::runtime::{LuaRuntime, LuaRuntimeInit},
+		module::Module,
+		theme::test_utils::test_theme,
+		types::color::Color,
+		utils::xdg::XdgDirs,
+	};
+
+	#[test]
+	fn create_and_apply() {
+		let tempdir = tempdir().unwrap();
+		let xdg = Arc::new(XdgDirs::in_tempdir(&tempdir));
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		let module_dir = xdg.config_home.join("niji/modules/test");
+
+		create_module(&module_dir, "test").unwrap();
+		create_module(&module_dir, "test").unwrap_err();
+		create_module(&xdg.config_home.join("niji/modules/x"), "../x").unwrap_err();
+
+		let runtime = LuaRuntime::new(LuaRuntimeInit {
+			xdg: xdg.clone(),
+			files,
+		})
+		.unwrap();
+		let module = Module::load(runtime, &module_dir, true).unwrap();
+		assert_eq!(module.manifest().name.as_deref(), Some("test"));
+		assert!(module.schema().unwrap().is_some());
+		assert!(module.can_reload());
+
+		module
+			.apply(ModuleConfig::new(), test_theme(), Color::BLACK)
+			.unwrap();
+		module.reload(ModuleConfig::new()).unwrap();
+		let colors = fs::read_to_string(xdg.config_home.join("test/niji-colors.conf")).unwrap();
+		assert!(colors.contains("accent = #000000\n"));
+	}
+}
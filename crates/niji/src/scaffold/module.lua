@@ -0,0 +1,25 @@
+local M = {}
+
+local template = niji.Template:load("colors.mustache")
+
+function M.apply(config, theme, accent)
+	local colors = template:render {
+		background = theme.ui.background,
+		foreground = theme.ui.text_default,
+		surface = theme.ui.surface,
+		text_surface = theme.ui:text_on(theme.ui.surface),
+		accent = accent,
+		text_accent = theme.ui:text_on(accent),
+		error = theme.ui.error,
+	}
+
+	niji.console.info("Installing niji theme for $MODULE_NAME...")
+	niji.fs.write_config("$MODULE_NAME/niji-colors.conf", colors)
+end
+
+function M.reload(config)
+	-- Make the application pick up the new colors here, for example:
+	-- os.execute("pkill -USR1 $MODULE_NAME")
+end
+
+return M
@@ -0,0 +1,264 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+
+use crate::config::{ModuleConfig, ModuleConfigValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionType {
+	String,
+	Int,
+	Float,
+	Bool,
+	List,
+	Map,
+	Any,
+}
+
+impl OptionType {
+	fn matches(self, value: &ModuleConfigValue) -> bool {
+		matches!(
+			(self, value),
+			(Self::Any, _)
+				| (Self::String, ModuleConfigValue::String(_))
+				| (Self::Int, ModuleConfigValue::Int(_))
+				| (
+					Self::Float,
+					ModuleConfigValue::Float(_) | ModuleConfigValue::Int(_)
+				) | (Self::Bool, ModuleConfigValue::Bool(_))
+				| (Self::List, ModuleConfigValue::Vec(_))
+				| (Self::Map, ModuleConfigValue::Map(_))
+		)
+	}
+}
+
+impl fmt::Display for OptionType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::String => write!(f, "string"),
+			Self::Int => write!(f, "int"),
+			Self::Float => write!(f, "float"),
+			Self::Bool => write!(f, "bool"),
+			Self::List => write!(f, "list"),
+			Self::Map => write!(f, "map"),
+			Self::Any => write!(f, "any"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OptionTypes {
+	Single(OptionType),
+	Multiple(Vec<OptionType>),
+}
+
+impl OptionTypes {
+	fn as_slice(&self) -> &[OptionType] {
+		match self {
+			Self::Single(ty) => std::slice::from_ref(ty),
+			Self::Multiple(types) => types,
+		}
+	}
+}
+
+impl fmt::Display for OptionTypes {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (i, ty) in self.as_slice().iter().enumerate() {
+			if i != 0 {
+				write!(f, " or ")?;
+			}
+			write!(f, "{ty}")?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OptionSchema {
+	#[serde(rename = "type")]
+	pub types: OptionTypes,
+
+	#[serde(default)]
+	pub default: Option<ModuleConfigValue>,
+
+	#[serde(default)]
+	pub description: Option<String>,
+
+	#[serde(default)]
+	pub values: Option<Vec<ModuleConfigValue>>,
+}
+
+/// The configuration options a module declares, keyed by their name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ConfigSchema(pub HashMap<String, OptionSchema>);
+
+impl ConfigSchema {
+	pub fn from_value(value: &ModuleConfigValue) -> anyhow::Result<Self> {
+		let value = toml::Value::try_from(value).context("Invalid config schema")?;
+		value.try_into().context("Invalid config schema")
+	}
+
+	/// Validates the merged `config` of `module` against the schema, and fills
+	/// in the defaults of options that aren't set.
+	///
+	/// Unknown keys only cause a warning if they come from the module-specific
+	/// config, since global options aren't necessarily used by every module.
+	pub fn validate(
+		&self,
+		module: &str,
+		config: &mut ModuleConfig,
+		specific: Option<&ModuleConfig>,
+	) -> anyhow::Result<()> {
+		if let Some(specific) = specific {
+			let mut unknown: Vec<&str> = specific
+				.keys()
+				.map(|key| key.trim_end_matches(['!', '+']))
+				.filter(|key| !self.0.contains_key(*key))
+				.collect();
+			unknown.sort_unstable();
+			for key in unknown {
+				log::warn!("Unknown option `{key}` for module {module}; it will be ignored.");
+			}
+		}
+
+		let mut errors: Vec<String> = Vec::new();
+		for (key, option) in &self.0 {
+			let Some(value) = config.get(key) else {
+				if let Some(default) = &option.default {
+					config.insert(key.clone(), default.clone());
+				}
+				continue;
+			};
+
+			if !option.types.as_slice().iter().any(|ty| ty.matches(value)) {
+				let description = option
+					.description
+					.as_ref()
+					.map_or_else(String::new, |d| format!(" ({d})"));
+				errors.push(format!(
+					"Option `{key}` must be of type {}, but got {}{description}",
+					option.types,
+					type_name(value)
+				));
+				continue;
+			}
+
+			if let Some(values) = &option.values
+				&& !values.contains(value)
+			{
+				errors.push(format!(
+					"Option `{key}` must be one of {}",
+					values
+						.iter()
+						.map(|v| toml::Value::try_from(v)
+							.map_or_else(|_| "nil".to_string(), |v| v.to_string()))
+						.collect::<Vec<_>>()
+						.join(", ")
+				));
+			}
+		}
+
+		if errors.is_empty() {
+			return Ok(());
+		}
+		errors.sort_unstable();
+		Err(anyhow!(
+			"Invalid configuration for module {module}:\n{}",
+			errors.join("\n")
+		))
+	}
+}
+
+fn type_name(value: &ModuleConfigValue) -> &'static str {
+	match value {
+		ModuleConfigValue::Nil => "nil",
+		ModuleConfigValue::String(_) => "string",
+		ModuleConfigValue::Int(_) => "int",
+		ModuleConfigValue::Float(_) => "float",
+		ModuleConfigValue::Bool(_) => "bool",
+		ModuleConfigValue::Vec(_) => "list",
+		ModuleConfigValue::Map(_) => "map",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SCHEMA: &str = r#"
+		show_shadow = { type = "bool", default = true }
+		padding_x = { type = "int", default = 12 }
+		font_scale = { type = "float" }
+		icon_font = { type = ["string", "bool"] }
+		position = { type = "string", values = ["top", "bottom"] }
+	"#;
+
+	fn schema() -> ConfigSchema {
+		toml::from_str(SCHEMA).unwrap()
+	}
+
+	fn config(str: &str) -> ModuleConfig {
+		toml::from_str(str).unwrap()
+	}
+
+	#[test]
+	fn fill_defaults() {
+		let mut merged = config("padding_x = 4");
+		schema().validate("test", &mut merged, None).unwrap();
+
+		assert!(matches!(
+			merged.get("show_shadow"),
+			Some(ModuleConfigValue::Bool(true))
+		));
+		assert!(matches!(
+			merged.get("padding_x"),
+			Some(ModuleConfigValue::Int(4))
+		));
+		assert!(!merged.contains_key("font_scale"));
+	}
+
+	#[test]
+	fn accept_valid_config() {
+		let specific =
+			config("font_scale = 2\nicon_font = false\nposition = \"top\"\nfont_sise = 12");
+		let mut merged = specific.clone();
+		schema()
+			.validate("test", &mut merged, Some(&specific))
+			.unwrap();
+	}
+
+	#[test]
+	fn reject_type_mismatch() {
+		let mut merged = config("show_shadow = \"yes\"\npadding_x = 1.5");
+		let err = schema().validate("test", &mut merged, None).unwrap_err();
+
+		assert_eq!(
+			err.to_string(),
+			"Invalid configuration for module test:\nOption `padding_x` must be of type int, but \
+			 got float\nOption `show_shadow` must be of type bool, but got string"
+		);
+	}
+
+	#[test]
+	fn reject_disallowed_value() {
+		let mut merged = config("position = \"left\"");
+		let err = schema().validate("test", &mut merged, None).unwrap_err();
+
+		assert_eq!(
+			err.to_string(),
+			"Invalid configuration for module test:\nOption `position` must be one of \"top\", \
+			 \"bottom\""
+		);
+	}
+
+	#[test]
+	fn from_value() {
+		let value: ModuleConfigValue = toml::from_str(SCHEMA).unwrap();
+		let schema = ConfigSchema::from_value(&value).unwrap();
+		assert_eq!(schema.0.len(), 5);
+	}
+}
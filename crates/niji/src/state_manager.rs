@@ -1,25 +1,57 @@
-use std::{fs, rc::Rc};
+use std::{fs, sync::Arc};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
 
-use crate::files::Files;
+use crate::{files::Files, utils::fs::write_atomic};
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(default)]
 struct State {
 	theme: Option<String>,
 	accent: Option<String>,
+	profile: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+	pub timestamp: String,
+	pub theme: Option<String>,
+	pub accent: Option<String>,
+	#[serde(default)]
+	pub profile: Option<String>,
+}
+
+impl HistoryEntry {
+	fn new(state: &State) -> Self {
+		Self {
+			timestamp: chrono::offset::Local::now().to_rfc3339(),
+			theme: state.theme.clone(),
+			accent: state.accent.clone(),
+			profile: state.profile.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct History {
+	position: usize,
+	entries: Vec<HistoryEntry>,
 }
 
 #[derive(Debug)]
 pub struct StateManager {
-	files: Rc<Files>,
+	files: Arc<Files>,
 	state: State,
+	history: History,
+	recorded: bool,
 }
 
 impl StateManager {
-	pub fn new(files: Rc<Files>) -> anyhow::Result<Self> {
+	const MAX_HISTORY_LEN: usize = 100;
+
+	pub fn new(files: Arc<Files>) -> anyhow::Result<Self> {
 		let mut state = State::default();
 		if files.state_file().exists() {
 			let state_str =
@@ -30,7 +62,30 @@ impl StateManager {
 			}
 		}
 
-		Ok(Self { files, state })
+		let mut history = History::default();
+		if files.history_file().exists() {
+			let history_str =
+				fs::read_to_string(files.history_file()).context("Failed to read history file")?;
+			match toml::from_str(&history_str) {
+				Ok(h) => history = h,
+				Err(err) => log::error!("Invalid history file: {err}\nResetting history."),
+			}
+		}
+		let last = history.entries.len().saturating_sub(1);
+		if history.position > last {
+			log::warn!(
+				"Invalid history position {}, using the latest entry instead.",
+				history.position
+			);
+			history.position = last;
+		}
+
+		Ok(Self {
+			files,
+			state,
+			history,
+			recorded: false,
+		})
 	}
 
 	pub fn get_theme(&self) -> Option<&str> {
@@ -41,29 +96,102 @@ impl StateManager {
 		self.state.accent.as_deref()
 	}
 
+	pub fn get_profile(&self) -> Option<&str> {
+		self.state.profile.as_deref()
+	}
+
+	pub fn get_history(&self) -> &[HistoryEntry] {
+		&self.history.entries
+	}
+
+	pub fn get_history_position(&self) -> usize {
+		self.history.position
+	}
+
 	pub fn set_theme(&mut self, theme: String) -> anyhow::Result<()> {
-		self.state.theme = Some(theme);
-		self.write_state()
+		self.update_state(|state| state.theme = Some(theme))
 	}
 
 	pub fn set_accent(&mut self, accent: String) -> anyhow::Result<()> {
-		self.state.accent = Some(accent);
-		self.write_state()
+		self.update_state(|state| state.accent = Some(accent))
+	}
+
+	pub fn set_profile(&mut self, profile: String) -> anyhow::Result<()> {
+		self.update_state(|state| state.profile = Some(profile))
 	}
 
 	pub fn unset_theme(&mut self) -> anyhow::Result<()> {
-		self.state.theme = None;
-		self.write_state()
+		self.update_state(|state| state.theme = None)
 	}
 
 	pub fn unset_accent(&mut self) -> anyhow::Result<()> {
-		self.state.accent = None;
-		self.write_state()
+		self.update_state(|state| state.accent = None)
+	}
+
+	pub fn undo(&mut self) -> anyhow::Result<()> {
+		if self.history.entries.is_empty() || self.history.position == 0 {
+			return Err(anyhow!("There is nothing to undo"));
+		}
+		self.restore_history_entry(self.history.position - 1)
+	}
+
+	pub fn redo(&mut self) -> anyhow::Result<()> {
+		if self.history.position + 1 >= self.history.entries.len() {
+			return Err(anyhow!("There is nothing to redo"));
+		}
+		self.restore_history_entry(self.history.position + 1)
+	}
+
+	fn restore_history_entry(&mut self, position: usize) -> anyhow::Result<()> {
+		let entry = &self.history.entries[position];
+		self.state.theme.clone_from(&entry.theme);
+		self.state.accent.clone_from(&entry.accent);
+		self.state.profile.clone_from(&entry.profile);
+		self.history.position = position;
+
+		self.write_state()?;
+		self.write_history()
+	}
+
+	fn update_state(&mut self, update: impl FnOnce(&mut State)) -> anyhow::Result<()> {
+		if self.history.entries.is_empty() {
+			// Remember the state from before the first recorded change, so that it can be restored
+			self.history.entries.push(HistoryEntry::new(&self.state));
+		}
+
+		update(&mut self.state);
+
+		// All changes made by a single invocation are grouped into one entry
+		let entry = HistoryEntry::new(&self.state);
+		if self.recorded {
+			*self.history.entries.last_mut().unwrap() = entry;
+		} else {
+			self.history.entries.truncate(self.history.position + 1);
+			self.history.entries.push(entry);
+			let excess = self
+				.history
+				.entries
+				.len()
+				.saturating_sub(Self::MAX_HISTORY_LEN);
+			self.history.entries.drain(..excess);
+			self.recorded = true;
+		}
+		self.history.position = self.history.entries.len() - 1;
+
+		self.write_state()?;
+		self.write_history()
 	}
 
 	fn write_state(&self) -> anyhow::Result<()> {
 		let state_str = toml::to_string(&self.state)?;
-		fs::write(self.files.state_file(), state_str).context("Failed to write state file")?;
+		write_atomic(self.files.state_file(), state_str).context("Failed to write state file")?;
+		Ok(())
+	}
+
+	fn write_history(&self) -> anyhow::Result<()> {
+		let history_str = toml::to_string(&self.history)?;
+		write_atomic(self.files.history_file(), history_str)
+			.context("Failed to write history file")?;
 		Ok(())
 	}
 }
@@ -80,7 +208,7 @@ mod tests {
 	fn get_state() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let files = Arc::new(Files::new(&xdg).unwrap());
 
 		fs::write(
 			xdg.state_home.join("niji/state.toml"),
@@ -97,7 +225,7 @@ mod tests {
 	fn set_state() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let files = Arc::new(Files::new(&xdg).unwrap());
 
 		fs::write(
 			xdg.state_home.join("niji/state.toml"),
@@ -123,7 +251,7 @@ mod tests {
 	fn get_initial_state() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let state_manager = StateManager::new(Rc::new(Files::new(&xdg).unwrap())).unwrap();
+		let state_manager = StateManager::new(Arc::new(Files::new(&xdg).unwrap())).unwrap();
 
 		assert!(state_manager.get_theme().is_none());
 		assert!(state_manager.get_accent().is_none());
@@ -133,7 +261,7 @@ mod tests {
 	fn set_initial_state() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let mut state_manager = StateManager::new(Rc::new(Files::new(&xdg).unwrap())).unwrap();
+		let mut state_manager = StateManager::new(Arc::new(Files::new(&xdg).unwrap())).unwrap();
 
 		state_manager.set_theme("some_theme".to_string()).unwrap();
 		state_manager.set_accent("some_color".to_string()).unwrap();
@@ -148,7 +276,7 @@ mod tests {
 	fn unset_current_theme() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let files = Rc::new(Files::new(&xdg).unwrap());
+		let files = Arc::new(Files::new(&xdg).unwrap());
 		fs::write(
 			xdg.state_home.join("niji/state.toml"),
 			"theme = \"some_theme\"\naccent = \"some_color\"",
@@ -164,4 +292,131 @@ mod tests {
 			"",
 		);
 	}
+
+	#[test]
+	fn set_profile() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let files = Arc::new(Files::new(&xdg).unwrap());
+
+		let mut state_manager = StateManager::new(Arc::clone(&files)).unwrap();
+		assert!(state_manager.get_profile().is_none());
+		state_manager.set_profile("work".to_string()).unwrap();
+
+		let mut state_manager = StateManager::new(files).unwrap();
+		assert_eq!(state_manager.get_profile(), Some("work"));
+		state_manager.undo().unwrap();
+		assert!(state_manager.get_profile().is_none());
+	}
+
+	#[test]
+	fn record_history() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let files = Arc::new(Files::new(&xdg).unwrap());
+
+		let mut state_manager = StateManager::new(Arc::clone(&files)).unwrap();
+		state_manager.set_theme("theme1".to_string()).unwrap();
+		state_manager.set_accent("color1".to_string()).unwrap();
+
+		let mut state_manager = StateManager::new(files).unwrap();
+		state_manager.set_theme("theme2".to_string()).unwrap();
+
+		let history = state_manager.get_history();
+		assert_eq!(history.len(), 3);
+		assert_eq!(history[0].theme, None);
+		assert_eq!(history[0].accent, None);
+		assert_eq!(history[1].theme.as_deref(), Some("theme1"));
+		assert_eq!(history[1].accent.as_deref(), Some("color1"));
+		assert_eq!(history[2].theme.as_deref(), Some("theme2"));
+		assert_eq!(history[2].accent.as_deref(), Some("color1"));
+		assert_eq!(state_manager.get_history_position(), 2);
+	}
+
+	#[test]
+	fn undo_redo() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let files = Arc::new(Files::new(&xdg).unwrap());
+
+		StateManager::new(Arc::clone(&files))
+			.unwrap()
+			.set_theme("theme1".to_string())
+			.unwrap();
+		StateManager::new(Arc::clone(&files))
+			.unwrap()
+			.set_theme("theme2".to_string())
+			.unwrap();
+
+		let mut state_manager = StateManager::new(Arc::clone(&files)).unwrap();
+		state_manager.undo().unwrap();
+		assert_eq!(state_manager.get_theme(), Some("theme1"));
+		state_manager.undo().unwrap();
+		assert_eq!(state_manager.get_theme(), None);
+		state_manager.undo().unwrap_err();
+		state_manager.redo().unwrap();
+		assert_eq!(state_manager.get_theme(), Some("theme1"));
+
+		let mut state_manager = StateManager::new(files).unwrap();
+		assert_eq!(state_manager.get_theme(), Some("theme1"));
+		state_manager.set_theme("theme3".to_string()).unwrap();
+		state_manager.redo().unwrap_err();
+		assert_eq!(state_manager.get_history().len(), 3);
+	}
+
+	#[test]
+	fn bounded_history() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let files = Arc::new(Files::new(&xdg).unwrap());
+
+		for i in 0..=StateManager::MAX_HISTORY_LEN {
+			StateManager::new(Arc::clone(&files))
+				.unwrap()
+				.set_theme(format!("theme{i}"))
+				.unwrap();
+		}
+
+		let state_manager = StateManager::new(files).unwrap();
+		let history = state_manager.get_history();
+		assert_eq!(history.len(), StateManager::MAX_HISTORY_LEN);
+		assert_eq!(
+			history.last().unwrap().theme,
+			Some(format!("theme{}", StateManager::MAX_HISTORY_LEN))
+		);
+	}
+
+	#[test]
+	fn invalid_history_position() {
+		let tempdir = tempdir().unwrap();
+		let xdg = XdgDirs::in_tempdir(&tempdir);
+		let files = Arc::new(Files::new(&xdg).unwrap());
+		fs::write(
+			xdg.state_home.join("niji/history.toml"),
+			r#"
+				position = 5
+
+				[[entries]]
+				timestamp = ""
+				theme = "theme1"
+
+				[[entries]]
+				timestamp = ""
+				theme = "theme2"
+			"#,
+		)
+		.unwrap();
+
+		let mut state_manager = StateManager::new(Arc::clone(&files)).unwrap();
+		assert_eq!(state_manager.get_history_position(), 1);
+		state_manager.redo().unwrap_err();
+		state_manager.undo().unwrap();
+		assert_eq!(state_manager.get_theme(), Some("theme1"));
+
+		fs::write(xdg.state_home.join("niji/history.toml"), "position = 3\n").unwrap();
+		let mut state_manager = StateManager::new(files).unwrap();
+		assert_eq!(state_manager.get_history_position(), 0);
+		state_manager.undo().unwrap_err();
+		state_manager.redo().unwrap_err();
+	}
 }
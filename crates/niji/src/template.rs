@@ -28,11 +28,51 @@ impl niji_templates::Format for Color {
 			"gf" => Some((f32::from(self.g) / 255.0).into()),
 			"bf" => Some((f32::from(self.b) / 255.0).into()),
 			"af" => Some((f32::from(self.a) / 255.0).into()),
+			"hsl_h" => Some(self.hsl().0.into()),
+			"hsl_s" => Some(self.hsl().1.into()),
+			"hsl_l" => Some(self.hsl().2.into()),
+			"hsv_h" => Some(self.hsv().0.into()),
+			"hsv_s" => Some(self.hsv().1.into()),
+			"hsv_v" => Some(self.hsv().2.into()),
+			"oklch_l" => Some(self.oklch().0.into()),
+			"oklch_c" => Some(self.oklch().1.into()),
+			"oklch_h" => Some(self.oklch().2.into()),
+			"rl" => Some(self.linear_rgb().0.into()),
+			"gl" => Some(self.linear_rgb().1.into()),
+			"bl" => Some(self.linear_rgb().2.into()),
+			"rp" => Some(self.premultiplied().r.into()),
+			"gp" => Some(self.premultiplied().g.into()),
+			"bp" => Some(self.premultiplied().b.into()),
+			"argb" => {
+				Some(format!("0x{:02x}{:02x}{:02x}{:02x}", self.a, self.r, self.g, self.b).into())
+			}
+			"css_rgb" => Some(format!("rgb({}, {}, {})", self.r, self.g, self.b).into()),
+			"css_rgba" => Some(
+				format!(
+					"rgba({}, {}, {}, {})",
+					self.r,
+					self.g,
+					self.b,
+					css_alpha(self.a)
+				)
+				.into(),
+			),
+			"ansi16" => Some(self.ansi16().into()),
+			"ansi256" => Some(self.ansi256().into()),
 			_ => None,
 		}
 	}
 }
 
+/// Formats an alpha channel as a float with at most three decimal places, as is customary in CSS
+fn css_alpha(alpha: u8) -> String {
+	let formatted = format!("{:.3}", f32::from(alpha) / 255.0);
+	formatted
+		.trim_end_matches('0')
+		.trim_end_matches('.')
+		.to_string()
+}
+
 pub fn load_template<P>(path: P) -> anyhow::Result<Template>
 where
 	P: AsRef<Path>,
@@ -64,4 +104,52 @@ mod tests {
 			 gf=0.20000000298023224; bf=0.3333333432674408; af=1"
 		);
 	}
+
+	#[test]
+	fn format_color_models() {
+		let color = Color::new_rgba(255, 128, 0, 255);
+		let result = color
+			.format(Some(
+				"hsl=({hsl_h:.0}, {hsl_s:.2}, {hsl_l:.2}); hsv=({hsv_h:.0}, {hsv_s:.2}, \
+				 {hsv_v:.2}); oklch=({oklch_l:.2}, {oklch_c:.2}, {oklch_h:.0})",
+			))
+			.unwrap();
+		assert_eq!(
+			result,
+			"hsl=(30, 1.00, 0.50); hsv=(30, 1.00, 1.00); oklch=(0.73, 0.19, 53)"
+		);
+	}
+
+	#[test]
+	fn format_color_linear_premultiplied() {
+		let color = Color::new_rgba(255, 128, 0, 128);
+		let result = color
+			.format(Some(
+				"rl={rl:.3}; gl={gl:.3}; bl={bl:.3}; rp={rp}; gp={gp}; bp={bp}",
+			))
+			.unwrap();
+		assert_eq!(result, "rl=1.000; gl=0.216; bl=0.000; rp=128; gp=64; bp=0");
+	}
+
+	#[test]
+	fn format_color_strings() {
+		let color = Color::new_rgba(17, 51, 85, 128);
+		let result = color
+			.format(Some("argb={argb}; css_rgb={css_rgb}; css_rgba={css_rgba}"))
+			.unwrap();
+		assert_eq!(
+			result,
+			"argb=0x80113355; css_rgb=rgb(17, 51, 85); css_rgba=rgba(17, 51, 85, 0.502)"
+		);
+	}
+
+	#[test]
+	fn format_color_ansi() {
+		let format = |color: Color| color.format(Some("{ansi16} {ansi256}")).unwrap();
+
+		assert_eq!(format(Color::new_rgba(0, 0, 0, 255)), "0 16");
+		assert_eq!(format(Color::new_rgba(255, 0, 0, 255)), "9 196");
+		assert_eq!(format(Color::new_rgba(128, 128, 128, 255)), "8 244");
+		assert_eq!(format(Color::new_rgba(255, 255, 255, 255)), "15 231");
+	}
 }
@@ -1,12 +1,19 @@
-use std::{collections::HashMap, fmt, fs, marker::PhantomData, path::Path, str::FromStr};
+use std::{
+	collections::HashMap,
+	fmt, fs,
+	hash::{Hash, Hasher},
+	marker::PhantomData,
+	path::Path,
+	str::FromStr,
+};
 
 use anyhow::{Context, anyhow};
-use serde::Deserialize;
-use serde_with::DeserializeFromStr;
+use serde::{Deserialize, Serialize};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 
 use crate::types::color::Color;
 
-#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Palette {
 	pub pink: Color,
 	pub red: Color,
@@ -45,6 +52,26 @@ impl Palette {
 	}
 }
 
+impl Hash for Palette {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.pink.hash(state);
+		self.red.hash(state);
+		self.orange.hash(state);
+		self.yellow.hash(state);
+		self.green.hash(state);
+		self.teal.hash(state);
+		self.blue.hash(state);
+		self.purple.hash(state);
+		self.black.hash(state);
+		self.white.hash(state);
+
+		// The iteration order of the custom colors is unspecified
+		let mut custom: Vec<_> = self.custom.iter().collect();
+		custom.sort_unstable_by_key(|(name, _)| *name);
+		custom.hash(state);
+	}
+}
+
 impl fmt::Display for Palette {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.write_str(&self.pink.preview())?;
@@ -62,7 +89,7 @@ impl fmt::Display for Palette {
 	}
 }
 
-#[derive(Debug, Clone, DeserializeFromStr)]
+#[derive(Debug, Clone, DeserializeFromStr, SerializeDisplay)]
 pub enum ColorRef {
 	Named(String),
 	Exact(Color),
@@ -304,7 +331,7 @@ impl<T> UiThemeSpec<T> {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize)]
 pub struct UiTheme {
 	pub background: Color,
 	pub surface: Color,
@@ -325,8 +352,10 @@ impl UiTheme {
 	}
 
 	pub fn text_on(&self, background: Color) -> Color {
-		let dark_contrast = self.text_dark.contrast(background);
-		let light_contrast = self.text_light.contrast(background);
+		// Translucent colors are assumed to be drawn on top of the theme background
+		let background = background.composite_over(self.background.with_alpha(1.0));
+		let dark_contrast = f32::abs(self.text_dark.apca(background));
+		let light_contrast = f32::abs(self.text_light.apca(background));
 
 		if dark_contrast >= light_contrast {
 			self.text_dark
@@ -526,7 +555,7 @@ impl<T> TerminalThemeSpec<T> {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize)]
 pub struct TerminalTheme {
 	pub default: Color,
 	pub dark_black: Color,
@@ -635,7 +664,8 @@ impl ThemeSpec {
 	}
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ThemeKind {
 	Light,
 	Dark,
@@ -650,7 +680,7 @@ impl fmt::Display for ThemeKind {
 	}
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize)]
 pub struct Theme {
 	pub name: String,
 	pub kind: ThemeKind,
@@ -743,4 +773,33 @@ mod tests {
 		);
 		assert!(palette.get("dsfsdfgaqsdea").is_err());
 	}
+
+	#[test]
+	fn text_on() {
+		let ui = UiTheme {
+			background: Color::from_str("#1e1e2e").unwrap(),
+			surface: Color::from_str("#313244").unwrap(),
+			border: Color::from_str("#45475a").unwrap(),
+			shadow: Color::from_str("#00000080").unwrap(),
+			text_light: Color::WHITE,
+			text_dark: Color::BLACK,
+			success: Color::from_str("#a6e3a1").unwrap(),
+			warning: Color::from_str("#f9e2af").unwrap(),
+			error: Color::from_str("#f38ba8").unwrap(),
+		};
+
+		assert_eq!(
+			ui.text_on(Color::from_str("#ff8800").unwrap()),
+			Color::BLACK
+		);
+		assert_eq!(
+			ui.text_on(Color::from_str("#009688").unwrap()),
+			Color::WHITE
+		);
+		assert_eq!(
+			ui.text_on(Color::from_str("#ffffff20").unwrap()),
+			Color::WHITE
+		);
+		assert_eq!(ui.text_default(), Color::WHITE);
+	}
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, path::PathBuf, rc::Rc};
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
 
 use anyhow::{Context, anyhow};
 use log::debug;
@@ -9,11 +9,11 @@ use crate::{
 };
 
 pub struct ThemeManager {
-	files: Rc<Files>,
+	files: Arc<Files>,
 }
 
 impl ThemeManager {
-	pub fn new(files: Rc<Files>) -> Self {
+	pub fn new(files: Arc<Files>) -> Self {
 		Self { files }
 	}
 
@@ -79,7 +79,7 @@ mod tests {
 	fn list_themes() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let theme_manager = ThemeManager::new(Rc::new(Files::new(&xdg).unwrap()));
+		let theme_manager = ThemeManager::new(Arc::new(Files::new(&xdg).unwrap()));
 
 		fs::write(xdg.config_home.join("niji/themes/theme1.toml"), "").unwrap();
 		fs::write(xdg.config_home.join("niji/themes/theme2.toml"), "").unwrap();
@@ -95,7 +95,7 @@ mod tests {
 	fn get_theme() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let theme_manager = ThemeManager::new(Rc::new(Files::new(&xdg).unwrap()));
+		let theme_manager = ThemeManager::new(Arc::new(Files::new(&xdg).unwrap()));
 
 		fs::write(
 			xdg.config_home.join("niji/themes/test_theme.toml"),
@@ -110,7 +110,7 @@ mod tests {
 	fn get_nonexistent_theme() {
 		let tempdir = tempdir().unwrap();
 		let xdg = XdgDirs::in_tempdir(&tempdir);
-		let theme_manager = ThemeManager::new(Rc::new(Files::new(&xdg).unwrap()));
+		let theme_manager = ThemeManager::new(Arc::new(Files::new(&xdg).unwrap()));
 
 		theme_manager.get_theme("theme1").unwrap_err();
 	}
@@ -0,0 +1,173 @@
+use std::{
+	ffi::OsString,
+	fs::{self, Permissions},
+	io,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+#[derive(Debug)]
+struct FileState {
+	contents: Vec<u8>,
+	permissions: Permissions,
+	xattrs: Vec<(OsString, Vec<u8>)>,
+}
+
+impl FileState {
+	fn read(path: &Path) -> io::Result<Self> {
+		let mut xattrs = Vec::new();
+		for name in list_xattrs(path)? {
+			if let Some(value) = xattr::get(path, &name)? {
+				xattrs.push((name, value));
+			}
+		}
+
+		Ok(Self {
+			contents: fs::read(path)?,
+			permissions: fs::metadata(path)?.permissions(),
+			xattrs,
+		})
+	}
+
+	fn restore(&self, path: &Path) -> io::Result<()> {
+		fs::write(path, &self.contents)?;
+		fs::set_permissions(path, self.permissions.clone())?;
+
+		for name in list_xattrs(path)? {
+			if !self.xattrs.iter().any(|(known, _)| *known == name) {
+				xattr::remove(path, &name)?;
+			}
+		}
+		for (name, value) in &self.xattrs {
+			xattr::set(path, name, value)?;
+		}
+		Ok(())
+	}
+}
+
+/// Lists the extended attributes of a file. Filesystems that don't support
+/// them are treated as if the file had none.
+fn list_xattrs(path: &Path) -> io::Result<Vec<OsString>> {
+	match xattr::list(path) {
+		Ok(names) => Ok(names.collect()),
+		Err(err) if err.kind() == io::ErrorKind::Unsupported => Ok(vec![]),
+		Err(err) => Err(err),
+	}
+}
+
+#[derive(Debug)]
+struct Snapshot {
+	path: PathBuf,
+
+	/// The state of the file before it was first written, or `None` if it
+	/// didn't exist.
+	previous: Option<FileState>,
+}
+
+/// The previous state of the files a module writes. While a lua state holds a
+/// `Transaction` as app data, the niji API takes a snapshot of every file
+/// before writing it for the first time, so that the changes can be rolled
+/// back if applying the module fails.
+#[derive(Debug, Default)]
+pub struct Transaction {
+	snapshots: Vec<Snapshot>,
+	created_dirs: Vec<PathBuf>,
+}
+
+impl Transaction {
+	/// Creates `dir` and its missing parents, remembering which directories
+	/// were created.
+	pub fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+		let missing: Vec<&Path> = dir.ancestors().take_while(|d| !d.exists()).collect();
+		fs::create_dir_all(dir)?;
+		self.created_dirs
+			.extend(missing.into_iter().rev().map(Path::to_path_buf));
+		Ok(())
+	}
+
+	/// Takes a snapshot of `path`, unless it was already written during this
+	/// transaction.
+	pub fn snapshot(&mut self, path: &Path) -> io::Result<()> {
+		if self.snapshots.iter().any(|snapshot| snapshot.path == path) {
+			return Ok(());
+		}
+
+		let previous = match FileState::read(path) {
+			Ok(state) => Some(state),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+			Err(err) => return Err(err),
+		};
+		self.snapshots.push(Snapshot {
+			path: path.to_path_buf(),
+			previous,
+		});
+		Ok(())
+	}
+
+	/// Restores all files to the state they had before they were first
+	/// written, and removes the files and directories that were created.
+	/// Returns the number of restored files.
+	pub fn rollback(self) -> anyhow::Result<usize> {
+		for snapshot in self.snapshots.iter().rev() {
+			match &snapshot.previous {
+				Some(state) => state.restore(&snapshot.path),
+				None => fs::remove_file(&snapshot.path).or_else(|err| {
+					if err.kind() == io::ErrorKind::NotFound {
+						Ok(())
+					} else {
+						Err(err)
+					}
+				}),
+			}
+			.context(format!("Failed to restore {}", snapshot.path.display()))?;
+		}
+
+		// Directories that other modules wrote to in the meantime aren't empty,
+		// and are kept
+		for dir in self.created_dirs.iter().rev() {
+			let _ = fs::remove_dir(dir);
+		}
+
+		Ok(self.snapshots.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	#[test]
+	fn rollback() {
+		let tempdir = tempdir().unwrap();
+		let existing = tempdir.path().join("existing.conf");
+		let new_dir = tempdir.path().join("new/dir");
+		let new = new_dir.join("new.conf");
+		fs::write(&existing, "old").unwrap();
+		xattr::set(&existing, "user.niji.test", b"old").unwrap();
+
+		let mut transaction = Transaction::default();
+		transaction.snapshot(&existing).unwrap();
+		fs::write(&existing, "new").unwrap();
+		xattr::set(&existing, "user.niji.test", b"new").unwrap();
+		xattr::set(&existing, "user.niji.other", b"new").unwrap();
+		transaction.snapshot(&existing).unwrap();
+		fs::write(&existing, "newer").unwrap();
+
+		transaction.create_dir_all(&new_dir).unwrap();
+		transaction.snapshot(&new).unwrap();
+		fs::write(&new, "new").unwrap();
+
+		assert_eq!(transaction.rollback().unwrap(), 2);
+
+		assert_eq!(fs::read_to_string(&existing).unwrap(), "old");
+		assert_eq!(
+			xattr::get(&existing, "user.niji.test").unwrap(),
+			Some(b"old".to_vec())
+		);
+		assert_eq!(xattr::get(&existing, "user.niji.other").unwrap(), None);
+		assert!(!tempdir.path().join("new").exists());
+	}
+}
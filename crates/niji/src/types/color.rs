@@ -4,16 +4,75 @@ use std::{fmt, mem::transmute, str::FromStr};
 
 use crate::utils::{lerp, oklch::OklchColor};
 
-// If the channel is negative something went *very* wrong somewhere else
+// The channel is clamped, so the value always fits into a u8
 #[allow(clippy::cast_sign_loss)]
-// The truncation is intended in this case; we only have a limited number of colors we can
-// represent after all
 #[allow(clippy::cast_possible_truncation)]
 fn discretize(channel: f32) -> u8 {
-	(channel * 255.0) as u8
+	(channel.clamp(0.0, 1.0) * 255.0).round() as u8
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, SerializeDisplay, DeserializeFromStr)]
+fn srgb_to_linear(channel: f32) -> f32 {
+	if channel <= 0.040_45 {
+		channel / 12.92
+	} else {
+		((channel + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+/// The default xterm colors for the 16 ANSI color indices. Terminals usually let users override
+/// these, so they're only an approximation of what will be displayed.
+const ANSI_16_COLORS: [Color; 16] = [
+	Color::new_rgba(0x00, 0x00, 0x00, 0xff),
+	Color::new_rgba(0xcd, 0x00, 0x00, 0xff),
+	Color::new_rgba(0x00, 0xcd, 0x00, 0xff),
+	Color::new_rgba(0xcd, 0xcd, 0x00, 0xff),
+	Color::new_rgba(0x00, 0x00, 0xee, 0xff),
+	Color::new_rgba(0xcd, 0x00, 0xcd, 0xff),
+	Color::new_rgba(0x00, 0xcd, 0xcd, 0xff),
+	Color::new_rgba(0xe5, 0xe5, 0xe5, 0xff),
+	Color::new_rgba(0x7f, 0x7f, 0x7f, 0xff),
+	Color::new_rgba(0xff, 0x00, 0x00, 0xff),
+	Color::new_rgba(0x00, 0xff, 0x00, 0xff),
+	Color::new_rgba(0xff, 0xff, 0x00, 0xff),
+	Color::new_rgba(0x5c, 0x5c, 0xff, 0xff),
+	Color::new_rgba(0xff, 0x00, 0xff, 0xff),
+	Color::new_rgba(0x00, 0xff, 0xff, 0xff),
+	Color::new_rgba(0xff, 0xff, 0xff, 0xff),
+];
+
+/// The channel levels used by the 6x6x6 color cube of the 256 color palette
+const ANSI_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn nearest_cube_level(channel: u8) -> u8 {
+	match channel {
+		0..48 => 0,
+		48..115 => 1,
+		_ => (channel - 35) / 40,
+	}
+}
+
+// Constants of the APCA-W3 0.0.98G-4g contrast algorithm
+const APCA_BLACK_THRESHOLD: f32 = 0.022;
+const APCA_BLACK_CLAMP: f32 = 1.414;
+const APCA_DELTA_Y_MIN: f32 = 0.0005;
+const APCA_SCALE: f32 = 1.14;
+const APCA_LOW_CLIP: f32 = 0.1;
+const APCA_LOW_OFFSET: f32 = 0.027;
+
+fn apca_luminance(color: Color) -> f32 {
+	let channel = |value: u8| (f32::from(value) / 255.0).powf(2.4);
+	let luminance = 0.212_672_9 * channel(color.r)
+		+ 0.715_152_2 * channel(color.g)
+		+ 0.072_175 * channel(color.b);
+
+	if luminance < APCA_BLACK_THRESHOLD {
+		luminance + (APCA_BLACK_THRESHOLD - luminance).powf(APCA_BLACK_CLAMP)
+	} else {
+		luminance
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SerializeDisplay, DeserializeFromStr)]
 #[repr(C, align(4))]
 pub struct Color {
 	pub a: u8,
@@ -40,8 +99,73 @@ impl Color {
 		self.into_oklch().lightness()
 	}
 
-	pub fn contrast(self, other: Color) -> f32 {
-		f32::abs(self.lightness() - other.lightness())
+	/// Returns the color that results from drawing this color over the provided background color.
+	pub fn composite_over(self, background: Self) -> Self {
+		let alpha = self.alpha();
+		let bg_alpha = background.alpha() * (1.0 - alpha);
+		let out_alpha = alpha + bg_alpha;
+		if out_alpha <= 0.0 {
+			return Self::TRANSPARENT;
+		}
+
+		let composite = |channel: u8, bg_channel: u8| {
+			discretize(
+				(f32::from(channel) * alpha + f32::from(bg_channel) * bg_alpha)
+					/ (255.0 * out_alpha),
+			)
+		};
+
+		Self::new_rgba(
+			composite(self.r, background.r),
+			composite(self.g, background.g),
+			composite(self.b, background.b),
+			discretize(out_alpha),
+		)
+	}
+
+	/// The relative luminance of the color as defined by WCAG 2.x
+	pub fn relative_luminance(self) -> f32 {
+		let (r, g, b) = self.linear_rgb();
+		0.2126 * r + 0.7152 * g + 0.0722 * b
+	}
+
+	/// The WCAG 2.x contrast ratio between two colors, ranging from 1 to 21. This color is
+	/// composited over `other` first, in case it is translucent.
+	pub fn contrast_ratio(self, other: Self) -> f32 {
+		let lum1 = self.composite_over(other).relative_luminance();
+		let lum2 = other.relative_luminance();
+		(lum1.max(lum2) + 0.05) / (lum1.min(lum2) + 0.05)
+	}
+
+	/// The APCA lightness contrast (Lc) of this color as text on the `background` color. The
+	/// result is positive for dark text on a light background, and negative for light text on a
+	/// dark background. This color is composited over the background first, in case it is
+	/// translucent.
+	pub fn apca(self, background: Self) -> f32 {
+		let text_y = apca_luminance(self.composite_over(background));
+		let bg_y = apca_luminance(background);
+
+		if f32::abs(bg_y - text_y) < APCA_DELTA_Y_MIN {
+			return 0.0;
+		}
+
+		let contrast = if bg_y > text_y {
+			let sapc = (bg_y.powf(0.56) - text_y.powf(0.57)) * APCA_SCALE;
+			if sapc < APCA_LOW_CLIP {
+				0.0
+			} else {
+				sapc - APCA_LOW_OFFSET
+			}
+		} else {
+			let sapc = (bg_y.powf(0.65) - text_y.powf(0.62)) * APCA_SCALE;
+			if sapc > -APCA_LOW_CLIP {
+				0.0
+			} else {
+				sapc + APCA_LOW_OFFSET
+			}
+		};
+
+		contrast * 100.0
 	}
 
 	pub fn lighten(self, amount: f32) -> Self {
@@ -75,10 +199,133 @@ impl Color {
 		Self::new_rgba(self.r, self.g, self.b, discretize(alpha))
 	}
 
+	/// Returns the hue in degrees, as well as the saturation and lightness of the color in the HSL
+	/// color model.
+	pub fn hsl(self) -> (f32, f32, f32) {
+		let (hue, min, max) = self.hue_min_max();
+		let lightness = f32::midpoint(max, min);
+		let saturation = if max <= min {
+			0.0
+		} else {
+			(max - min) / (1.0 - f32::abs(2.0 * lightness - 1.0))
+		};
+		(hue, saturation, lightness)
+	}
+
+	/// Returns the hue in degrees, as well as the saturation and value of the color in the HSV
+	/// color model.
+	pub fn hsv(self) -> (f32, f32, f32) {
+		let (hue, min, max) = self.hue_min_max();
+		let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+		(hue, saturation, max)
+	}
+
+	/// Returns the lightness, chroma and hue in degrees of the color in the OKLCH color space.
+	pub fn oklch(self) -> (f32, f32, f32) {
+		let oklch = self.into_oklch();
+		(
+			oklch.lightness(),
+			oklch.chroma(),
+			oklch.hue().to_degrees().rem_euclid(360.0),
+		)
+	}
+
+	/// Returns the linear-light values of the red, green and blue channels
+	pub fn linear_rgb(self) -> (f32, f32, f32) {
+		let (r, g, b) = self.rgb_f32();
+		(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+	}
+
+	/// Returns the color with the red, green and blue channels multiplied by its alpha value.
+	pub fn premultiplied(self) -> Self {
+		let alpha = self.alpha();
+		let (r, g, b) = self.rgb_f32();
+		Self::new_rgba(
+			discretize(r * alpha),
+			discretize(g * alpha),
+			discretize(b * alpha),
+			self.a,
+		)
+	}
+
+	/// Returns the index of the perceptually closest color among the 16 basic ANSI colors,
+	/// assuming the default xterm palette.
+	pub fn ansi16(self) -> u8 {
+		let oklch = self.into_oklch();
+		let (index, _) = ANSI_16_COLORS
+			.iter()
+			.zip(0..)
+			.map(|(color, i)| (i, oklch.distance(color.into_oklch())))
+			.min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+			.unwrap();
+		index
+	}
+
+	/// Returns the index of the perceptually closest color in the 6x6x6 color cube or the
+	/// grayscale ramp of the 256 color ANSI palette. The 16 basic colors are never chosen, since
+	/// their exact values depend on the terminal configuration.
+	pub fn ansi256(self) -> u8 {
+		let (ri, gi, bi) = (
+			nearest_cube_level(self.r),
+			nearest_cube_level(self.g),
+			nearest_cube_level(self.b),
+		);
+		let cube_color = Self::new_rgba(
+			ANSI_CUBE_LEVELS[usize::from(ri)],
+			ANSI_CUBE_LEVELS[usize::from(gi)],
+			ANSI_CUBE_LEVELS[usize::from(bi)],
+			0xff,
+		);
+		let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+		let average = (u16::from(self.r) + u16::from(self.g) + u16::from(self.b)) / 3;
+		// The grayscale ramp has 24 steps from 8 to 238 in increments of 10
+		#[allow(clippy::cast_possible_truncation)]
+		let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+		let gray_level = 8 + 10 * gray_step;
+		let gray_color = Self::new_rgba(gray_level, gray_level, gray_level, 0xff);
+		let gray_index = 232 + gray_step;
+
+		let oklch = self.into_oklch();
+		if oklch.distance(gray_color.into_oklch()) < oklch.distance(cube_color.into_oklch()) {
+			gray_index
+		} else {
+			cube_index
+		}
+	}
+
 	pub fn preview(self) -> String {
 		format!("\x1b[48;2;{};{};{}m   \x1b[0m", self.r, self.g, self.b)
 	}
 
+	fn rgb_f32(self) -> (f32, f32, f32) {
+		(
+			f32::from(self.r) / 255.0,
+			f32::from(self.g) / 255.0,
+			f32::from(self.b) / 255.0,
+		)
+	}
+
+	fn hue_min_max(self) -> (f32, f32, f32) {
+		let (r, g, b) = self.rgb_f32();
+		let max = r.max(g).max(b);
+		let min = r.min(g).min(b);
+		let delta = max - min;
+
+		#[allow(clippy::float_cmp)]
+		let hue = if delta == 0.0 {
+			0.0
+		} else if max == r {
+			60.0 * ((g - b) / delta).rem_euclid(6.0)
+		} else if max == g {
+			60.0 * ((b - r) / delta + 2.0)
+		} else {
+			60.0 * ((r - g) / delta + 4.0)
+		};
+
+		(hue, min, max)
+	}
+
 	fn into_oklch(self) -> OklchColor {
 		OklchColor::from_srgb(self.r, self.g, self.b)
 	}
@@ -176,6 +423,27 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn composite_over() {
+		let col = Color::from(0xff_00_00_80).composite_over(Color::from(0x00_00_ff_ff));
+
+		assert_eq!(col, Color::from(0x80_00_7f_ff));
+	}
+
+	#[test]
+	fn contrast_ratio() {
+		assert!(f32::abs(Color::BLACK.contrast_ratio(Color::WHITE) - 21.0) < 0.01);
+		assert!(f32::abs(Color::WHITE.contrast_ratio(Color::WHITE) - 1.0) < 0.01);
+		assert!(f32::abs(Color::from(0x00_00_00_00).contrast_ratio(Color::WHITE) - 1.0) < 0.01);
+	}
+
+	#[test]
+	fn apca() {
+		assert!(f32::abs(Color::BLACK.apca(Color::WHITE) - 106.04) < 0.1);
+		assert!(f32::abs(Color::WHITE.apca(Color::BLACK) + 107.88) < 0.1);
+		assert!(f32::abs(Color::from(0x88_88_88_ff).apca(Color::from(0x88_88_88_ff))) < 0.01);
+	}
+
 	#[test]
 	fn should_parse_8_len() {
 		assert_eq!(
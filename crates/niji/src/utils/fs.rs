@@ -1,6 +1,8 @@
 use std::{
-	fs::{ReadDir, read_dir},
-	io,
+	env,
+	fs::{self, File, ReadDir, read_dir},
+	io::{self, Write},
+	os::unix::fs::PermissionsExt,
 	path::{Path, PathBuf},
 	result::Result,
 };
@@ -135,3 +137,63 @@ where
 {
 	DirIter::new(FindSubPathsIter::new(search_paths.into_iter()))
 }
+
+/// Writes `contents` to `path` by writing to a temporary file in the same
+/// directory first, and then renaming it, so that readers never observe a
+/// partially written file.
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+	let path = path.as_ref();
+	let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+	tmp_name.push(".tmp");
+	let tmp_path = path.with_file_name(tmp_name);
+
+	let mut file = File::create(&tmp_path)?;
+	file.write_all(contents.as_ref())?;
+	file.sync_all()?;
+	drop(file);
+
+	fs::rename(&tmp_path, path)
+}
+
+/// Looks up an executable in the directories in `$PATH`, like `which`.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+	if name.contains('/') {
+		let path = PathBuf::from(name);
+		return is_executable(&path).then_some(path);
+	}
+
+	let path_var = env::var_os("PATH")?;
+	env::split_paths(&path_var)
+		.map(|dir| dir.join(name))
+		.find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+	fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	#[test]
+	fn write_atomic_replaces_file() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("file.toml");
+		fs::write(&path, "old").unwrap();
+
+		write_atomic(&path, "new").unwrap();
+
+		assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+		assert_eq!(fs::read_dir(tempdir.path()).unwrap().count(), 1);
+	}
+
+	#[test]
+	fn find_program_in_path() {
+		assert!(find_program("sh").is_some());
+		assert!(find_program("/bin/sh").is_some());
+		assert!(find_program("niji-nonexistent-program").is_none());
+	}
+}
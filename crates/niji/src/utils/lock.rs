@@ -0,0 +1,62 @@
+use std::{
+	fs::{File, TryLockError},
+	path::Path,
+};
+
+use anyhow::{Context, anyhow};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+	Wait,
+	NoWait,
+}
+
+/// An advisory lock that prevents multiple niji processes from modifying the
+/// state or applying modules at the same time. It is released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+	_file: File,
+}
+
+impl FileLock {
+	pub fn acquire(path: &Path, mode: LockMode) -> anyhow::Result<Self> {
+		let file = File::create(path)
+			.with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+		match file.try_lock() {
+			Ok(()) => return Ok(Self { _file: file }),
+			Err(TryLockError::Error(err)) => {
+				return Err(err).context("Failed to acquire lock");
+			}
+			Err(TryLockError::WouldBlock) => (),
+		}
+
+		if mode == LockMode::NoWait {
+			return Err(anyhow!(
+				"Another niji process is currently running. Use `--wait` to wait for it to finish."
+			));
+		}
+
+		log::info!("Waiting for another niji process to finish...");
+		file.lock().context("Failed to acquire lock")?;
+		Ok(Self { _file: file })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tempfile::tempdir;
+
+	use super::*;
+
+	#[test]
+	fn exclusive_lock() {
+		let tempdir = tempdir().unwrap();
+		let path = tempdir.path().join("niji.lock");
+
+		let lock = FileLock::acquire(&path, LockMode::NoWait).unwrap();
+		FileLock::acquire(&path, LockMode::NoWait).unwrap_err();
+		drop(lock);
+		FileLock::acquire(&path, LockMode::NoWait).unwrap();
+	}
+}
@@ -1,4 +1,5 @@
 pub mod fs;
+pub mod lock;
 pub mod oklch;
 pub mod xdg;
 
@@ -1,7 +1,35 @@
-use oklab::{Oklab, Rgb, oklab_to_srgb, srgb_to_oklab};
+use oklab::{
+	LinearRgb, Oklab, Rgb, linear_srgb_to_oklab, oklab_to_linear_srgb, oklab_to_srgb, srgb_to_oklab,
+};
 
 use crate::utils::lerp;
 
+/// The largest color difference that is considered imperceptible when reducing chroma to map a
+/// color into the sRGB gamut, as defined in CSS Color 4
+const GAMUT_MAPPING_JND: f32 = 0.02;
+
+/// The precision with which the chroma is searched for when mapping colors into the sRGB gamut
+const GAMUT_MAPPING_EPSILON: f32 = 0.0001;
+
+fn delta_e(lab1: Oklab, lab2: Oklab) -> f32 {
+	f32::sqrt((lab1.l - lab2.l).powi(2) + (lab1.a - lab2.a).powi(2) + (lab1.b - lab2.b).powi(2))
+}
+
+fn is_in_gamut(lab: Oklab) -> bool {
+	let LinearRgb { r, g, b } = oklab_to_linear_srgb(lab);
+	let range = -GAMUT_MAPPING_EPSILON..=1.0 + GAMUT_MAPPING_EPSILON;
+	range.contains(&r) && range.contains(&g) && range.contains(&b)
+}
+
+fn clip(lab: Oklab) -> Oklab {
+	let LinearRgb { r, g, b } = oklab_to_linear_srgb(lab);
+	linear_srgb_to_oklab(LinearRgb {
+		r: r.clamp(0.0, 1.0),
+		g: g.clamp(0.0, 1.0),
+		b: b.clamp(0.0, 1.0),
+	})
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct OklchColor {
 	l: f32,
@@ -26,16 +54,76 @@ impl OklchColor {
 	}
 
 	pub fn into_srgb(self) -> (u8, u8, u8) {
-		// Convert to cartesian representation
-		let a = self.c * f32::cos(self.h);
-		let b = self.c * f32::sin(self.h);
-		let lab = Oklab { l: self.l, a, b };
-
-		let rgb = oklab_to_srgb(lab);
+		let rgb = oklab_to_srgb(self.gamut_mapped());
 
 		(rgb.r, rgb.g, rgb.b)
 	}
 
+	/// Maps the color into the sRGB gamut using the algorithm from CSS Color 4; chroma is reduced
+	/// until clipping the color results in an imperceptible difference, so that lightness and hue
+	/// are preserved.
+	fn gamut_mapped(self) -> Oklab {
+		if self.l >= 1.0 {
+			return Oklab {
+				l: 1.0,
+				a: 0.0,
+				b: 0.0,
+			};
+		}
+		if self.l <= 0.0 {
+			return Oklab {
+				l: 0.0,
+				a: 0.0,
+				b: 0.0,
+			};
+		}
+
+		let origin = self.into_oklab();
+		if is_in_gamut(origin) {
+			return origin;
+		}
+
+		let mut clipped = clip(origin);
+		if delta_e(clipped, origin) < GAMUT_MAPPING_JND {
+			return clipped;
+		}
+
+		let mut min = 0.0;
+		let mut max = self.c;
+		let mut min_in_gamut = true;
+		let mut current = self;
+
+		while max - min > GAMUT_MAPPING_EPSILON {
+			current.c = f32::midpoint(min, max);
+			let lab = current.into_oklab();
+
+			if min_in_gamut && is_in_gamut(lab) {
+				min = current.c;
+				continue;
+			}
+
+			clipped = clip(lab);
+			let error = delta_e(clipped, lab);
+			if error < GAMUT_MAPPING_JND {
+				if GAMUT_MAPPING_JND - error < GAMUT_MAPPING_EPSILON {
+					break;
+				}
+				min_in_gamut = false;
+				min = current.c;
+			} else {
+				max = current.c;
+			}
+		}
+
+		clipped
+	}
+
+	fn into_oklab(self) -> Oklab {
+		// Convert to cartesian representation
+		let (a, b) = self.cartesian();
+		Oklab { l: self.l, a, b }
+	}
+
 	#[inline]
 	pub fn lightness(self) -> f32 {
 		self.l
@@ -51,6 +139,13 @@ impl OklchColor {
 		self.h
 	}
 
+	/// The euclidean distance between two colors in the Oklab color space
+	pub fn distance(self, other: Self) -> f32 {
+		let (a1, b1) = self.cartesian();
+		let (a2, b2) = other.cartesian();
+		f32::sqrt((self.l - other.l).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2))
+	}
+
 	pub fn shade(self, lightness: f32) -> Self {
 		let mut result = self;
 		result.l = lightness;
@@ -65,6 +160,10 @@ impl OklchColor {
 		self.shade(self.lightness() - amount)
 	}
 
+	fn cartesian(self) -> (f32, f32) {
+		(self.c * f32::cos(self.h), self.c * f32::sin(self.h))
+	}
+
 	pub fn blend(col1: Self, col2: Self, t: f32) -> Self {
 		Self::new(
 			lerp(col1.lightness(), col2.lightness(), t),
@@ -84,4 +183,22 @@ mod tests {
 
 		assert_eq!(color.into_srgb(), (174, 49, 39));
 	}
+
+	#[test]
+	fn gamut_mapping_preserves_hue() {
+		let color = OklchColor::from_srgb(0, 0, 255);
+		let (r, g, b) = color.lighten(0.2).into_srgb();
+		let mapped = OklchColor::from_srgb(r, g, b);
+
+		assert!(f32::abs(mapped.hue() - color.hue()) < 0.05);
+		assert!(f32::abs(mapped.lightness() - color.lighten(0.2).lightness()) < 0.02);
+	}
+
+	#[test]
+	fn gamut_mapping_extreme_lightness() {
+		let color = OklchColor::from_srgb(0, 0, 255);
+
+		assert_eq!(color.shade(1.2).into_srgb(), (255, 255, 255));
+		assert_eq!(color.shade(-0.1).into_srgb(), (0, 0, 0));
+	}
 }
@@ -71,6 +71,22 @@ impl XdgDirs {
 			),
 		})
 	}
+
+	/// Returns a copy of these directories with the user-specific ones moved
+	/// to `root`, so that the user's configuration isn't visible. The system
+	/// data directories are kept, so that the built-in themes and modules can
+	/// still be found.
+	pub fn sandboxed(&self, root: &Path) -> Self {
+		Self {
+			config_home: root.join(".config"),
+			data_home: root.join(".local/share"),
+			state_home: root.join(".local/state"),
+			cache_home: root.join(".cache"),
+			runtime_dir: None,
+			data_dirs: self.data_dirs.clone(),
+			config_dirs: vec![root.join("etc/xdg")],
+		}
+	}
 }
 
 #[cfg(test)]
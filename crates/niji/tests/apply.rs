@@ -0,0 +1,155 @@
+use std::fs;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use tempfile::tempdir;
+
+#[test]
+fn dry_run_failed_module() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let module_dir = config_dir.path().join("niji/modules/foo");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env(
+				"XDG_DATA_DIRS",
+				concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+			);
+		cmd
+	};
+
+	fs::create_dir_all(&module_dir).unwrap();
+	fs::write(
+		config_dir.path().join("niji/config.toml"),
+		"modules = [\"foo\"]\n",
+	)
+	.unwrap();
+	fs::write(
+		module_dir.join("module.lua"),
+		r#"
+			return {
+				apply = function()
+					local file = io.open("out.txt", "w")
+					file:write("partial\n")
+					file:close()
+					error("something went wrong")
+				end
+			}
+		"#,
+	)
+	.unwrap();
+	niji()
+		.args(["theme", "set", "--no-apply", "dracula", "--accent", "blue"])
+		.assert()
+		.success();
+
+	// The changes recorded before the failure are still shown
+	let output = niji()
+		.args(["apply", "--dry-run"])
+		.assert()
+		.failure()
+		.get_output()
+		.stdout
+		.clone();
+	assert!(String::from_utf8_lossy(&output).contains("+partial"));
+	assert!(!module_dir.join("out.txt").exists());
+}
+
+#[test]
+fn lua_hook_output() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env(
+				"XDG_DATA_DIRS",
+				concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+			);
+		cmd
+	};
+
+	fs::create_dir_all(config_dir.path().join("niji")).unwrap();
+	fs::write(
+		config_dir.path().join("niji/config.toml"),
+		"modules = []\n[hooks]\npost_apply = [{ lua = \"hook.lua\" }]\n",
+	)
+	.unwrap();
+	fs::write(
+		config_dir.path().join("niji/hook.lua"),
+		"print('hello from', (...).event)\n",
+	)
+	.unwrap();
+	niji()
+		.args(["theme", "set", "--no-apply", "dracula", "--accent", "blue"])
+		.assert()
+		.success();
+
+	// The hook output doesn't end up in the JSON output
+	let output = niji()
+		.args(["--output", "json", "apply"])
+		.assert()
+		.success()
+		.get_output()
+		.clone();
+	assert_eq!(
+		String::from_utf8_lossy(&output.stdout).trim(),
+		r#"{"modules":[]}"#
+	);
+	assert!(String::from_utf8_lossy(&output.stderr).contains("hello from\tpost_apply"));
+}
+
+#[test]
+fn module_print_output() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let modules_dir = config_dir.path().join("niji/modules");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env(
+				"XDG_DATA_DIRS",
+				concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+			);
+		cmd
+	};
+
+	for name in ["a", "b"] {
+		fs::create_dir_all(modules_dir.join(name)).unwrap();
+		fs::write(
+			modules_dir.join(name).join("module.lua"),
+			format!("return {{ apply = function() print('from {name}', 1, nil) end }}"),
+		)
+		.unwrap();
+	}
+	fs::write(
+		config_dir.path().join("niji/config.toml"),
+		"modules = [\"a\", \"b\"]\n",
+	)
+	.unwrap();
+	niji()
+		.args(["theme", "set", "--no-apply", "dracula", "--accent", "blue"])
+		.assert()
+		.success();
+
+	// The printed lines are buffered with the rest of the module's output
+	let output = niji()
+		.args(["apply", "--no-color"])
+		.assert()
+		.success()
+		.get_output()
+		.stdout
+		.clone();
+	let output = String::from_utf8_lossy(&output);
+	assert!(
+		output.contains("==== [ a ] ====\nfrom a\t1\tnil\n"),
+		"{output}"
+	);
+	assert!(
+		output.contains("==== [ b ] ====\nfrom b\t1\tnil\n"),
+		"{output}"
+	);
+}
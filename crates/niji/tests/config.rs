@@ -0,0 +1,137 @@
+use std::fs;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use tempfile::tempdir;
+
+#[test]
+fn config_overrides() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let config_file = config_dir.path().join("alternate.toml");
+	fs::write(
+		&config_file,
+		"modules = []\n[profiles.work]\ntheme = \"gruvbox\"",
+	)
+	.unwrap();
+
+	cargo_bin_cmd!("niji")
+		.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+		.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+		.env(
+			"NIJI_CONFIG__PROFILES__PRESENTATION__THEME",
+			"gruvbox-light",
+		)
+		.args([
+			"--config",
+			&config_file.to_string_lossy(),
+			"--set",
+			"profiles.daily.accent=\"blue\"",
+			"profile",
+			"list",
+		])
+		.assert()
+		.success()
+		.stdout("daily\npresentation\nwork\n");
+}
+
+#[test]
+fn missing_config_file() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str());
+		cmd
+	};
+
+	// The default config file may be missing, but not one that was passed
+	niji().arg("status").assert().success();
+	niji()
+		.args([
+			"--config",
+			&config_dir.path().join("missing.toml").to_string_lossy(),
+			"status",
+		])
+		.assert()
+		.failure();
+}
+
+#[test]
+fn invalid_override() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+
+	cargo_bin_cmd!("niji")
+		.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+		.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+		.args(["--set", "modules", "profile", "list"])
+		.assert()
+		.failure();
+}
+
+#[test]
+fn set_get_unset() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let config_file = config_dir.path().join("niji/config.toml");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str());
+		cmd
+	};
+
+	niji()
+		.args(["config", "path"])
+		.assert()
+		.success()
+		.stdout(format!("{}\n", config_file.display()));
+
+	fs::create_dir_all(config_file.parent().unwrap()).unwrap();
+	fs::write(&config_file, "# comment\nmodules = []\n").unwrap();
+
+	niji()
+		.args(["config", "set", "kitty.background_opacity", "0.9"])
+		.assert()
+		.success();
+	niji()
+		.args(["config", "get", "kitty.background_opacity"])
+		.assert()
+		.success()
+		.stdout("0.9\n");
+	niji()
+		.args(["config", "set", "modules", "42"])
+		.assert()
+		.failure();
+	niji()
+		.args(["config", "unset", "kitty.background_opacity"])
+		.assert()
+		.success();
+	niji()
+		.args(["config", "get", "kitty.background_opacity"])
+		.assert()
+		.failure();
+
+	assert_eq!(
+		fs::read_to_string(&config_file).unwrap(),
+		"# comment\nmodules = []\n\n[kitty]\n"
+	);
+}
+
+#[test]
+fn edit() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let config_file = config_dir.path().join("config.toml");
+
+	cargo_bin_cmd!("niji")
+		.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+		.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+		.env("VISUAL", "echo 'modules = []' >")
+		.args(["--config", &config_file.to_string_lossy(), "config", "edit"])
+		.assert()
+		.success();
+
+	assert_eq!(fs::read_to_string(&config_file).unwrap(), "modules = []\n");
+}
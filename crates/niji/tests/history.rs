@@ -0,0 +1,28 @@
+use std::fs;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use tempfile::tempdir;
+
+#[test]
+fn empty_history() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	fs::create_dir_all(config_dir.path().join("niji")).unwrap();
+	fs::write(config_dir.path().join("niji/config.toml"), "modules = []\n").unwrap();
+
+	for command in ["history", "undo", "redo"] {
+		let output = cargo_bin_cmd!("niji")
+			.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.arg(command)
+			.assert()
+			.success()
+			.get_output()
+			.stdout
+			.clone();
+		assert!(
+			String::from_utf8_lossy(&output)
+				.contains("No changes to the theme or accent color were recorded yet")
+		);
+	}
+}
@@ -0,0 +1,131 @@
+use std::fs;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use tempfile::tempdir;
+
+#[test]
+fn list_enable_disable() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let data_dir = tempdir().unwrap();
+	let config_file = config_dir.path().join("niji/config.toml");
+	let modules_dir = config_dir.path().join("niji/modules");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env("XDG_DATA_DIRS", data_dir.path().as_os_str());
+		cmd
+	};
+
+	fs::create_dir_all(modules_dir.join("bar")).unwrap();
+	fs::create_dir_all(modules_dir.join("foo")).unwrap();
+	fs::write(
+		modules_dir.join("foo/module.toml"),
+		"description = \"The foo module\"\nversion = \"1.0.0\"\n",
+	)
+	.unwrap();
+	fs::write(&config_file, "modules = [\"foo\"]\n").unwrap();
+
+	niji()
+		.args(["module", "list"])
+		.assert()
+		.success()
+		.stdout(format!(
+			"  bar  {}\n* foo  {}\n",
+			modules_dir.join("bar").display(),
+			modules_dir.join("foo").display()
+		));
+	niji()
+		.args(["module", "path", "foo"])
+		.assert()
+		.success()
+		.stdout(format!("{}\n", modules_dir.join("foo").display()));
+	let info = niji()
+		.args(["module", "info", "foo"])
+		.assert()
+		.success()
+		.get_output()
+		.stdout
+		.clone();
+	assert!(String::from_utf8(info).unwrap().contains("The foo module"));
+
+	niji().args(["module", "enable", "baz"]).assert().failure();
+	niji().args(["module", "enable", "bar"]).assert().success();
+	niji().args(["module", "disable", "foo"]).assert().success();
+	niji().args(["module", "disable", "foo"]).assert().failure();
+
+	assert_eq!(
+		fs::read_to_string(&config_file).unwrap(),
+		"modules = [\"bar\"]\n"
+	);
+}
+
+#[test]
+fn new_module() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let data_dir = tempdir().unwrap();
+	let module_dir = config_dir.path().join("niji/modules/foo");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env("XDG_DATA_DIRS", data_dir.path().as_os_str());
+		cmd
+	};
+
+	niji().args(["module", "new", "foo"]).assert().success();
+	niji().args(["module", "new", "foo"]).assert().failure();
+
+	for file in [
+		"module.lua",
+		"module.toml",
+		"colors.mustache",
+		"tests/default.toml",
+	] {
+		assert!(module_dir.join(file).exists(), "{file}");
+	}
+	assert!(
+		fs::read_to_string(module_dir.join("module.lua"))
+			.unwrap()
+			.contains("niji.fs.write_config(\"foo/niji-colors.conf\", colors)")
+	);
+	niji().args(["module", "info", "foo"]).assert().success();
+}
+
+#[test]
+fn test_module() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let module_dir = config_dir.path().join("niji/modules/foo");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+			.env("XDG_STATE_HOME", state_dir.path().as_os_str())
+			.env(
+				"XDG_DATA_DIRS",
+				concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+			);
+		cmd
+	};
+
+	niji().args(["module", "new", "foo"]).assert().success();
+	niji().args(["module", "test", "foo"]).assert().failure();
+	niji()
+		.args(["module", "test", "foo", "--update"])
+		.assert()
+		.success();
+	niji().args(["module", "test", "foo"]).assert().success();
+	assert!(
+		module_dir
+			.join("tests/snapshots/default/dracula.snap")
+			.exists()
+	);
+
+	fs::write(module_dir.join("tests/default.toml"), "accent = \"red\"\n").unwrap();
+	niji()
+		.args(["module", "test", "foo", "--theme", "dracula"])
+		.assert()
+		.failure();
+}
@@ -0,0 +1,135 @@
+use std::fs;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use serde_json::{Value, json};
+use tempfile::tempdir;
+
+#[test]
+fn json_output() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env(
+			"XDG_DATA_DIRS",
+			concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+		)
+		.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+		.env("XDG_STATE_HOME", state_dir.path().as_os_str());
+		cmd
+	};
+	let json = |args: &[&str]| -> Value {
+		let output = niji()
+			.args(["--output", "json"])
+			.args(args)
+			.assert()
+			.success()
+			.get_output()
+			.stdout
+			.clone();
+		serde_json::from_slice(&output).unwrap()
+	};
+
+	fs::create_dir_all(config_dir.path().join("niji")).unwrap();
+	fs::write(config_dir.path().join("niji/config.toml"), "modules = []\n").unwrap();
+
+	assert_eq!(
+		json(&["status"]),
+		json!({ "theme": null, "kind": null, "accent": null, "profile": null })
+	);
+
+	let themes = json(&["theme", "list"]);
+	assert!(themes.as_array().unwrap().contains(&json!("dracula")));
+
+	niji()
+		.args(["theme", "set", "--no-apply", "dracula", "--accent", "blue"])
+		.assert()
+		.success();
+
+	assert_eq!(
+		json(&["status"]),
+		json!({ "theme": "dracula", "kind": "dark", "accent": "blue", "profile": null })
+	);
+	assert_eq!(
+		json(&["accent", "get"]),
+		json!({ "accent": "blue", "color": "#8be9fdff" })
+	);
+
+	let theme = json(&["theme", "get"]);
+	assert_eq!(theme["name"], "dracula");
+	assert_eq!(theme["palette"]["blue"], "#8be9fdff");
+
+	assert_eq!(json(&["apply"]), json!({ "modules": [] }));
+}
+
+#[test]
+fn json_output_without_module_output() {
+	let config_dir = tempdir().unwrap();
+	let state_dir = tempdir().unwrap();
+	let module_dir = config_dir.path().join("niji/modules/foo");
+	let niji = || {
+		let mut cmd = cargo_bin_cmd!("niji");
+		cmd.env(
+			"XDG_DATA_DIRS",
+			concat!(env!("CARGO_MANIFEST_DIR"), "/tests/theme.in"),
+		)
+		.env("XDG_CONFIG_HOME", config_dir.path().as_os_str())
+		.env("XDG_STATE_HOME", state_dir.path().as_os_str());
+		cmd
+	};
+	let json = |args: &[&str]| -> Value {
+		let output = niji()
+			.args(["--output", "json"])
+			.args(args)
+			.assert()
+			.success()
+			.get_output()
+			.stdout
+			.clone();
+		serde_json::from_slice(&output).unwrap()
+	};
+
+	fs::create_dir_all(&module_dir).unwrap();
+	fs::write(
+		config_dir.path().join("niji/config.toml"),
+		"modules = [\"foo\"]\n[foo]\nopacity = 0.5\n[profiles.work]\ntheme = \"dracula\"\n",
+	)
+	.unwrap();
+	fs::write(
+		module_dir.join("module.lua"),
+		r#"
+			return {
+				apply = function()
+					print("printed")
+					os.execute("echo from os.execute")
+					io.popen("cat", "w"):write("from io.popen\n"):close()
+				end
+			}
+		"#,
+	)
+	.unwrap();
+
+	assert_eq!(json(&["history"]), json!([]));
+	niji()
+		.args(["theme", "set", "--no-apply", "dracula", "--accent", "blue"])
+		.assert()
+		.success();
+
+	// Only the report ends up in stdout
+	let report = json(&["apply"]);
+	assert_eq!(report["modules"][0]["status"], "applied");
+
+	let history = json(&["history"]);
+	let latest = history.as_array().unwrap().last().unwrap();
+	assert_eq!(latest["theme"], "dracula");
+	assert_eq!(latest["current"], true);
+	assert_eq!(json(&["profile", "list"]), json!(["work"]));
+	assert_eq!(json(&["config", "get", "foo.opacity"]), json!(0.5));
+	assert_eq!(
+		json(&["module", "path", "foo"]),
+		json!({ "path": module_dir })
+	);
+	let info = json(&["module", "info", "foo"]);
+	assert_eq!(info["name"], "foo");
+	assert_eq!(info["enabled"], true);
+}
@@ -1,11 +1,28 @@
-use std::fmt::Arguments;
+use std::{cell::RefCell, fmt::Arguments, rc::Rc};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
-use crate::console::Console;
+use crate::{
+	capture::{CaptureStream, Captured, Chunk, Stream},
+	console::Console,
+};
 
 static CONSOLE: RwLock<Option<Console>> = RwLock::new(None);
 
+/// Held while prompting the user or printing captured output, so that the
+/// output of other threads doesn't end up in the middle of a prompt.
+static INTERACTION: Mutex<()> = Mutex::new(());
+
+struct Capture {
+	title: String,
+	chunks: Rc<RefCell<Vec<Chunk>>>,
+	console: Console<(), CaptureStream>,
+}
+
+thread_local! {
+	static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
 pub(crate) fn set_console(console: Console) {
 	CONSOLE.write().replace(console);
 }
@@ -14,11 +31,18 @@ pub(crate) fn use_console<T>(cb: impl FnOnce(&Console) -> T) -> Option<T> {
 	CONSOLE.read().as_ref().map(cb)
 }
 
+fn use_capture<T>(cb: impl FnOnce(&Console<(), CaptureStream>) -> T) -> Option<T> {
+	CAPTURE.with_borrow(|capture| capture.as_ref().map(|capture| cb(&capture.console)))
+}
+
 macro_rules! api_fn {
 	($fn:ident($($arg:ident : $ty:ty),*) -> $out:ty : $default:expr) => {
         #[doc = "# Errors"]
         #[doc = "Returns an error when no console is configured"]
         pub fn $fn($($arg: $ty),*) -> ::anyhow::Result<$out> {
+            if let Some(result) = use_capture(|console| console.$fn($($arg),*)) {
+                return result;
+            }
             let result = use_console(|console| console.$fn($($arg),*)).unwrap_or(Ok($default))?;
             Ok(result)
         }
@@ -30,7 +54,78 @@ api_fn!(log_warn(args: &Arguments) -> () : ());
 api_fn!(log_info(args: &Arguments) -> () : ());
 api_fn!(log_debug(args: &Arguments) -> () : ());
 api_fn!(log_trace(args: &Arguments) -> () : ());
-api_fn!(prompt(args: &Arguments, default: Option<bool>) -> bool : default.unwrap_or(false));
 api_fn!(heading(args: &Arguments) -> () : ());
 api_fn!(println(args: Option<&Arguments>) -> () : ());
 api_fn!(flush() -> () : ());
+
+/// Prompts are never captured. If the current thread is capturing its output,
+/// the output captured so far is printed before the prompt, so that the user
+/// knows what they are being asked about.
+///
+/// # Errors
+/// Returns an error when no console is configured
+pub fn prompt(args: &Arguments, default: Option<bool>) -> anyhow::Result<bool> {
+	let _interaction = INTERACTION.lock();
+
+	let captured = CAPTURE.with_borrow(|capture| {
+		capture.as_ref().map(|capture| Captured {
+			title: capture.title.clone(),
+			chunks: capture.chunks.take(),
+		})
+	});
+	if let Some(captured) = captured {
+		print_captured_unlocked(&captured)?;
+	}
+
+	let result = use_console(|console| console.prompt(args, default))
+		.unwrap_or(Ok(default.unwrap_or(false)))?;
+	Ok(result)
+}
+
+/// Runs `cb`, buffering all console output it produces on the current thread
+/// instead of printing it. The buffered output can be printed later under a
+/// heading with the given title using [`print_captured`].
+pub fn capture<T>(title: &str, cb: impl FnOnce() -> T) -> (T, Captured) {
+	let (out_color, err_color) = use_console(Console::supports_color).unwrap_or_default();
+	let chunks = Rc::new(RefCell::new(Vec::new()));
+	let capture = Capture {
+		title: title.to_string(),
+		chunks: Rc::clone(&chunks),
+		console: Console::new(
+			(),
+			CaptureStream::new(Stream::Out, out_color, Rc::clone(&chunks)),
+			CaptureStream::new(Stream::Err, err_color, Rc::clone(&chunks)),
+		),
+	};
+
+	let prev_capture = CAPTURE.replace(Some(capture));
+	let result = cb();
+	CAPTURE.set(prev_capture);
+
+	let captured = Captured {
+		title: title.to_string(),
+		chunks: chunks.take(),
+	};
+	(result, captured)
+}
+
+/// Prints output buffered by [`capture`] under a heading.
+///
+/// # Errors
+/// Returns an error when no console is configured
+pub fn print_captured(captured: &Captured) -> anyhow::Result<()> {
+	let _interaction = INTERACTION.lock();
+	print_captured_unlocked(captured)
+}
+
+fn print_captured_unlocked(captured: &Captured) -> anyhow::Result<()> {
+	use_console(|console| {
+		console.heading(&format_args!("{}", captured.title))?;
+		console.flush()?;
+		for chunk in &captured.chunks {
+			console.print_chunk(chunk)?;
+		}
+		console.flush()
+	})
+	.unwrap_or(Ok(()))
+}
@@ -0,0 +1,127 @@
+use std::{cell::RefCell, io, rc::Rc};
+
+use termcolor::{Buffer, ColorSpec, WriteColor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stream {
+	Out,
+	Err,
+}
+
+#[derive(Debug)]
+pub(crate) struct Chunk {
+	pub stream: Stream,
+	pub buffer: Buffer,
+}
+
+/// Console output that was buffered by [`crate::api::capture`], and can be
+/// printed later using [`crate::api::print_captured`].
+#[derive(Debug)]
+pub struct Captured {
+	pub(crate) title: String,
+	pub(crate) chunks: Vec<Chunk>,
+}
+
+/// One of the two output streams of a capturing console. Both streams append
+/// to the same list of chunks, so that the order of their output is kept.
+pub(crate) struct CaptureStream {
+	stream: Stream,
+	color: bool,
+	chunks: Rc<RefCell<Vec<Chunk>>>,
+}
+
+impl CaptureStream {
+	pub fn new(stream: Stream, color: bool, chunks: Rc<RefCell<Vec<Chunk>>>) -> Self {
+		Self {
+			stream,
+			color,
+			chunks,
+		}
+	}
+
+	fn with_buffer<T>(&self, cb: impl FnOnce(&mut Buffer) -> T) -> T {
+		let mut chunks = self.chunks.borrow_mut();
+		if chunks
+			.last()
+			.is_none_or(|chunk| chunk.stream != self.stream)
+		{
+			chunks.push(Chunk {
+				stream: self.stream,
+				buffer: if self.color {
+					Buffer::ansi()
+				} else {
+					Buffer::no_color()
+				},
+			});
+		}
+		cb(&mut chunks.last_mut().unwrap().buffer)
+	}
+}
+
+impl io::Write for CaptureStream {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.with_buffer(|buffer| buffer.write(buf))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl WriteColor for CaptureStream {
+	fn supports_color(&self) -> bool {
+		self.color
+	}
+
+	fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+		self.with_buffer(|buffer| buffer.set_color(spec))
+	}
+
+	fn reset(&mut self) -> io::Result<()> {
+		self.with_buffer(WriteColor::reset)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::console::Console;
+
+	use super::*;
+
+	#[test]
+	fn keep_stream_order() {
+		let chunks = Rc::new(RefCell::new(Vec::new()));
+		let console = Console::new(
+			(),
+			CaptureStream::new(Stream::Out, false, Rc::clone(&chunks)),
+			CaptureStream::new(Stream::Err, false, Rc::clone(&chunks)),
+		);
+
+		console.log_info(&format_args!("Test #1")).unwrap();
+		console.log_info(&format_args!("Test #2")).unwrap();
+		console.log_error(&format_args!("Test #3")).unwrap();
+		console.println(None).unwrap();
+
+		let chunks: Vec<(Stream, String)> = chunks
+			.take()
+			.into_iter()
+			.map(|chunk| {
+				(
+					chunk.stream,
+					String::from_utf8_lossy(chunk.buffer.as_slice()).into_owned(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			chunks,
+			vec![
+				(
+					Stream::Out,
+					" INFO - Test #1\n INFO - Test #2\n".to_string()
+				),
+				(Stream::Err, "ERROR - Test #3\n".to_string()),
+				(Stream::Out, "\n".to_string()),
+			]
+		);
+	}
+}
@@ -6,6 +6,8 @@ use std::{
 use parking_lot::Mutex;
 use termcolor::{BufferedStandardStream, Color, ColorChoice, ColorSpec, WriteColor};
 
+use crate::capture::{Chunk, Stream};
+
 pub struct Console<I = BufReader<io::Stdin>, O = BufferedStandardStream> {
 	input: Mutex<I>,
 	output: Mutex<O>,
@@ -34,6 +36,22 @@ impl Console {
 	}
 }
 
+impl Console {
+	pub fn new_stderr(color_choice: ColorChoice) -> Self {
+		let color_choice = if color_choice == ColorChoice::Auto && !io::stderr().is_terminal() {
+			ColorChoice::Never
+		} else {
+			color_choice
+		};
+
+		Self::new(
+			BufReader::new(io::stdin()),
+			BufferedStandardStream::stderr(color_choice),
+			BufferedStandardStream::stderr(color_choice),
+		)
+	}
+}
+
 impl<I, O> Console<I, O> {
 	pub fn new(input: I, output: O, err_output: O) -> Self {
 		Self {
@@ -185,6 +203,25 @@ where
 		Ok(())
 	}
 
+	pub(crate) fn supports_color(&self) -> (bool, bool) {
+		(
+			self.output.lock().supports_color(),
+			self.err_output.lock().supports_color(),
+		)
+	}
+
+	pub(crate) fn print_chunk(&self, chunk: &Chunk) -> anyhow::Result<()> {
+		let out = &mut match chunk.stream {
+			Stream::Out => &self.output,
+			Stream::Err => &self.err_output,
+		}
+		.lock();
+
+		out.write_all(chunk.buffer.as_slice())?;
+		out.flush()?;
+		Ok(())
+	}
+
 	pub fn flush(&self) -> anyhow::Result<()> {
 		let stdout = &mut self.output.lock();
 		let stderr = &mut self.err_output.lock();
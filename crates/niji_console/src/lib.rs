@@ -1,9 +1,11 @@
+pub use capture::Captured;
 use console::Console;
 pub use log::LevelFilter;
 use logger::Logger;
 pub use termcolor::ColorChoice;
 
 pub mod api;
+mod capture;
 mod console;
 mod logger;
 
@@ -13,7 +15,19 @@ mod macros;
 /// # Panics
 /// panics if a logger has already been set
 pub fn init(level: LevelFilter, color_choice: ColorChoice) {
-	let console = Console::new_std(color_choice);
+	init_with(Console::new_std(color_choice), level);
+}
+
+/// Like [`init`], but writes all output to stderr, so that stdout can be used
+/// for machine-readable output.
+///
+/// # Panics
+/// panics if a logger has already been set
+pub fn init_stderr(level: LevelFilter, color_choice: ColorChoice) {
+	init_with(Console::new_stderr(color_choice), level);
+}
+
+fn init_with(console: Console, level: LevelFilter) {
 	if level != LevelFilter::Off {
 		api::set_console(console);
 	}